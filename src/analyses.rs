@@ -0,0 +1,131 @@
+use crate::analysis::OutputFormat;
+use crate::prompts::PromptTemplate;
+use crate::structured::{ActionItems, KeyPoints, Participants, StructuredOutput};
+use serde_json::Value;
+
+// Longest ad-hoc instructions accepted with a request
+pub const MAX_INSTRUCTIONS_CHARS: usize = 8000;
+
+// The analyses the server has always offered, each with its own route and storage directory
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BuiltinAnalysis {
+    Summary,
+    KeyPoints,
+    ActionItems,
+    Participants,
+}
+
+impl BuiltinAnalysis {
+    pub const ALL: [BuiltinAnalysis; 4] = [
+        BuiltinAnalysis::Summary,
+        BuiltinAnalysis::KeyPoints,
+        BuiltinAnalysis::ActionItems,
+        BuiltinAnalysis::Participants,
+    ];
+
+    pub fn from_name(name: &str) -> Option<BuiltinAnalysis> {
+        Self::ALL
+            .into_iter()
+            .find(|analysis| analysis.name() == name)
+    }
+
+    // Analysis type name, also used as the prompt template name and artifact kind
+    pub fn name(self) -> &'static str {
+        match self {
+            BuiltinAnalysis::Summary => "summary",
+            BuiltinAnalysis::KeyPoints => "key_points",
+            BuiltinAnalysis::ActionItems => "action_items",
+            BuiltinAnalysis::Participants => "participants",
+        }
+    }
+
+    pub fn directory(self) -> &'static str {
+        match self {
+            BuiltinAnalysis::Summary => "summaries",
+            other => other.name(),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BuiltinAnalysis::Summary => "summary",
+            BuiltinAnalysis::KeyPoints => "key points",
+            BuiltinAnalysis::ActionItems => "action items",
+            BuiltinAnalysis::Participants => "participants",
+        }
+    }
+
+    // Error message clients have always received when the model call fails
+    pub fn failure_message(self) -> String {
+        match self {
+            BuiltinAnalysis::Summary => "Error generating summary".to_string(),
+            other => format!("Error extracting {}", other.label()),
+        }
+    }
+
+    pub fn format(self) -> Option<OutputFormat> {
+        match self {
+            BuiltinAnalysis::Summary => None,
+            BuiltinAnalysis::KeyPoints => Some(KeyPoints::FORMAT),
+            BuiltinAnalysis::ActionItems => Some(ActionItems::FORMAT),
+            BuiltinAnalysis::Participants => Some(Participants::FORMAT),
+        }
+    }
+
+    // Turn model output into the text saved as `.txt` and, for structured analyses, the JSON data
+    pub fn parse(self, output: String) -> Result<(String, Option<Value>), String> {
+        match self {
+            BuiltinAnalysis::Summary => Ok((output, None)),
+            BuiltinAnalysis::KeyPoints => parse_structured::<KeyPoints>(&output),
+            BuiltinAnalysis::ActionItems => parse_structured::<ActionItems>(&output),
+            BuiltinAnalysis::Participants => parse_structured::<Participants>(&output),
+        }
+    }
+
+    // Items of a structured result saved as `{directory}/{id}.json`
+    pub fn stored_items(self, json: &[u8]) -> Option<Vec<String>> {
+        match self {
+            BuiltinAnalysis::Summary => None,
+            BuiltinAnalysis::KeyPoints => stored_items::<KeyPoints>(json),
+            BuiltinAnalysis::ActionItems => stored_items::<ActionItems>(json),
+            BuiltinAnalysis::Participants => stored_items::<Participants>(json),
+        }
+    }
+}
+
+fn stored_items<T: StructuredOutput>(json: &[u8]) -> Option<Vec<String>> {
+    serde_json::from_slice::<T>(json)
+        .ok()
+        .map(|data| data.items())
+}
+
+fn parse_structured<T: StructuredOutput>(output: &str) -> Result<(String, Option<Value>), String> {
+    let data = serde_json::from_str::<T>(output).map_err(|e| e.to_string())?;
+    let value = serde_json::to_value(&data).map_err(|e| e.to_string())?;
+    Ok((data.render_text(), Some(value)))
+}
+
+// What to run: one of the built-in analyses, or free-form text output driven by a named
+// template from the prompts directory or by instructions sent with the request
+pub enum AnalysisType {
+    Builtin(BuiltinAnalysis),
+    Custom(PromptTemplate),
+}
+
+impl AnalysisType {
+    // Artifact kind recorded for results of this analysis
+    pub fn kind(&self) -> String {
+        match self {
+            AnalysisType::Builtin(analysis) => analysis.name().to_string(),
+            AnalysisType::Custom(template) if template.is_inline() => "instructions".to_string(),
+            AnalysisType::Custom(template) => format!("template:{}", template.name),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            AnalysisType::Builtin(analysis) => analysis.label().to_string(),
+            AnalysisType::Custom(template) => template.name.replace('_', " "),
+        }
+    }
+}
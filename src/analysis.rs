@@ -0,0 +1,716 @@
+use crate::transcript::{
+    format_timestamp, has_speakers, speaker_turns, timestamped_text, turn_text, TranscriptSegment,
+};
+use crate::usage::{self, UsageMeter};
+use futures::stream::{self, StreamExt};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::env;
+use std::fmt;
+
+// Chat model used for all transcript analyses
+pub const CHAT_MODEL: &str = "gpt-4o-mini";
+
+// Transcripts above this many tokens are analysed part by part and the results merged
+const DEFAULT_MAX_INPUT_TOKENS: usize = 24_000;
+
+// Target size of each part when a transcript is split up
+const DEFAULT_CHUNK_TOKENS: usize = 8_000;
+
+// How many parts are sent to the model at the same time
+const DEFAULT_MAP_CONCURRENCY: usize = 4;
+
+// How many times structured output is requested before giving up on invalid JSON
+const DEFAULT_OUTPUT_ATTEMPTS: usize = 3;
+
+fn config_value(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(default)
+}
+
+// Base URL of the OpenAI-compatible API, overridable for proxies and local testing
+pub fn openai_base_url() -> String {
+    env::var("OPENAI_BASE_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|_| "https://api.openai.com/v1".to_string())
+}
+
+// Rough token count; OpenAI tokenizers average about four characters per token for English text
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+#[derive(Debug)]
+pub enum AnalysisError {
+    Request(reqwest::Error),
+    // The API answered with an error status or an error object
+    Api {
+        status: u16,
+        code: Option<String>,
+        message: String,
+    },
+    // The model stopped for a reason other than finishing its answer, e.g. `length` or
+    // `content_filter`
+    Incomplete(String),
+    Refused(String),
+    EmptyResponse,
+    InvalidOutput(String),
+    // The consumer of a streamed analysis went away; holds the output generated so far
+    Cancelled(String),
+}
+
+impl fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalysisError::Request(e) => write!(f, "request to the model failed: {}", e),
+            AnalysisError::Api {
+                status,
+                code: Some(code),
+                message,
+            } => write!(f, "model API returned {} ({}): {}", status, code, message),
+            AnalysisError::Api {
+                status, message, ..
+            } => write!(f, "model API returned {}: {}", status, message),
+            AnalysisError::Incomplete(reason) if reason == "length" => {
+                write!(f, "model output was cut off at its length limit")
+            }
+            AnalysisError::Incomplete(reason) if reason == "content_filter" => {
+                write!(f, "model output was blocked by the content filter")
+            }
+            AnalysisError::Incomplete(reason) => {
+                write!(f, "model stopped before finishing ({})", reason)
+            }
+            AnalysisError::Refused(refusal) => write!(f, "model refused: {}", refusal),
+            AnalysisError::EmptyResponse => write!(f, "model returned no output"),
+            AnalysisError::InvalidOutput(e) => write!(f, "model returned invalid output: {}", e),
+            AnalysisError::Cancelled(_) => write!(f, "stopped after the client disconnected"),
+        }
+    }
+}
+
+impl std::error::Error for AnalysisError {}
+
+impl From<reqwest::Error> for AnalysisError {
+    fn from(e: reqwest::Error) -> Self {
+        AnalysisError::Request(e)
+    }
+}
+
+// A JSON schema the model must answer with, and the check applied to what comes back
+#[derive(Clone, Copy)]
+pub struct OutputFormat {
+    pub name: &'static str,
+    pub schema: fn() -> Value,
+    pub validate: fn(&str) -> Result<(), String>,
+}
+
+impl OutputFormat {
+    fn response_format(&self) -> Value {
+        json!({
+            "type": "json_schema",
+            "json_schema": {
+                "name": self.name,
+                "strict": true,
+                "schema": (self.schema)()
+            }
+        })
+    }
+}
+
+// Output of a streamed analysis as it is generated
+pub enum OutputEvent<'a> {
+    Delta(&'a str),
+    // Status of a long analysis while its parts are processed before anything is streamed
+    Progress(&'a str),
+    // Discard the output so far; structured output is being requested again after failing validation
+    Restart,
+}
+
+// Receives streamed output; returns false once nobody is listening any more
+pub type OutputSink<'a> = &'a dyn Fn(OutputEvent<'_>) -> bool;
+
+// Everything needed to run one analysis over a transcript
+pub struct AnalysisRequest<'a> {
+    pub transcript: String,
+    pub segments: Option<&'a [TranscriptSegment]>,
+    pub system_message: &'a str,
+    pub format: Option<OutputFormat>,
+    // Set to stream the final model call's output while it is generated
+    pub sink: Option<OutputSink<'a>>,
+    // Receives the token usage of every model call the analysis makes
+    pub usage: &'a UsageMeter,
+}
+
+// Turn an error response into an `AnalysisError`, using the `{"error": {...}}` object OpenAI
+// sends when there is one
+fn api_error(status: u16, body: &str) -> AnalysisError {
+    let error = serde_json::from_str::<Value>(body)
+        .map(|v| v["error"].clone())
+        .unwrap_or(Value::Null);
+    let message = match error["message"].as_str() {
+        Some(message) => message.to_string(),
+        None if body.trim().is_empty() => "empty response body".to_string(),
+        None => body.chars().take(200).collect(),
+    };
+    let code = error["code"]
+        .as_str()
+        .or_else(|| error["type"].as_str())
+        .map(str::to_string);
+    AnalysisError::Api {
+        status,
+        code,
+        message,
+    }
+}
+
+// Anything but `stop` means the output is not a complete answer and must not be used. A
+// missing reason means the response was cut short before the model said why it stopped.
+fn check_finish_reason(finish_reason: Option<&str>) -> Result<(), AnalysisError> {
+    match finish_reason {
+        Some("stop") => Ok(()),
+        Some(reason) => Err(AnalysisError::Incomplete(reason.to_string())),
+        None => Err(AnalysisError::Incomplete("no finish_reason".to_string())),
+    }
+}
+
+// Helper function to call OpenAI API with the conversation so far
+pub async fn call_openai_api(
+    messages: Vec<Value>,
+    response_format: Option<Value>,
+    usage: &UsageMeter,
+) -> Result<String, AnalysisError> {
+    let client = Client::new();
+    let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY must be set");
+
+    let mut request_body = json!({
+        "model": CHAT_MODEL,
+        "temperature": 0.0,
+        "messages": messages
+    });
+    if let Some(response_format) = response_format {
+        request_body["response_format"] = response_format;
+    }
+
+    let response = client
+        .post(format!("{}/chat/completions", openai_base_url()))
+        .bearer_auth(&api_key)
+        .json(&request_body)
+        .send()
+        .await?;
+
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        return Err(api_error(status.as_u16(), &body));
+    }
+    let json_response =
+        serde_json::from_str::<Value>(&body).map_err(|_| api_error(status.as_u16(), &body))?;
+    if !json_response["error"].is_null() {
+        return Err(api_error(status.as_u16(), &body));
+    }
+    if let Some(entry) = usage::chat_usage(CHAT_MODEL, &api_key, &json_response["usage"]) {
+        usage.add(entry);
+    }
+
+    let choice = &json_response["choices"][0];
+    if let Some(refusal) = choice["message"]["refusal"].as_str() {
+        return Err(AnalysisError::Refused(refusal.to_string()));
+    }
+    check_finish_reason(choice["finish_reason"].as_str())?;
+    match choice["message"]["content"].as_str() {
+        Some(content) if !content.trim().is_empty() => Ok(content.to_string()),
+        _ => Err(AnalysisError::EmptyResponse),
+    }
+}
+
+// Same request as `call_openai_api`, but with `stream: true`: each content delta is passed to
+// the sink as it arrives and the assembled text is returned at the end
+async fn stream_openai_api(
+    messages: Vec<Value>,
+    response_format: Option<Value>,
+    sink: OutputSink<'_>,
+    usage: &UsageMeter,
+) -> Result<String, AnalysisError> {
+    let client = Client::new();
+    let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY must be set");
+
+    // Usage arrives in a final chunk with no choices when asked for
+    let mut request_body = json!({
+        "model": CHAT_MODEL,
+        "temperature": 0.0,
+        "stream": true,
+        "stream_options": { "include_usage": true },
+        "messages": messages
+    });
+    if let Some(response_format) = response_format {
+        request_body["response_format"] = response_format;
+    }
+
+    let response = client
+        .post(format!("{}/chat/completions", openai_base_url()))
+        .bearer_auth(&api_key)
+        .json(&request_body)
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await?;
+        return Err(api_error(status.as_u16(), &body));
+    }
+
+    // Server-sent events: `data: {json}` lines, terminated by `data: [DONE]`
+    let mut body = response.bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut output = String::new();
+    let mut refusal = String::new();
+    let mut finish_reason: Option<String> = None;
+    while let Some(bytes) = body.next().await {
+        buffer.extend_from_slice(&bytes?);
+        while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                continue;
+            };
+            let data = data.trim();
+            if data == "[DONE]" {
+                return finish_stream(output, refusal, finish_reason);
+            }
+            let Ok(event) = serde_json::from_str::<Value>(data) else {
+                continue;
+            };
+            if !event["error"].is_null() {
+                return Err(api_error(status.as_u16(), data));
+            }
+            if let Some(reason) = event["choices"][0]["finish_reason"].as_str() {
+                finish_reason = Some(reason.to_string());
+            }
+            if let Some(delta) = event["choices"][0]["delta"]["refusal"].as_str() {
+                refusal.push_str(delta);
+            }
+            if let Some(entry) = usage::chat_usage(CHAT_MODEL, &api_key, &event["usage"]) {
+                usage.add(entry);
+            }
+            if let Some(delta) = event["choices"][0]["delta"]["content"].as_str() {
+                output.push_str(delta);
+                if !sink(OutputEvent::Delta(delta)) {
+                    return Err(AnalysisError::Cancelled(output));
+                }
+            }
+        }
+    }
+    // The connection closed before the terminating event, so the answer may be partial
+    Err(AnalysisError::Incomplete(
+        "stream ended before [DONE]".to_string(),
+    ))
+}
+
+// Apply the same checks to a streamed answer as `call_openai_api` does to a complete one
+fn finish_stream(
+    output: String,
+    refusal: String,
+    finish_reason: Option<String>,
+) -> Result<String, AnalysisError> {
+    if !refusal.is_empty() {
+        return Err(AnalysisError::Refused(refusal));
+    }
+    check_finish_reason(finish_reason.as_deref())?;
+    if output.trim().is_empty() {
+        return Err(AnalysisError::EmptyResponse);
+    }
+    Ok(output)
+}
+
+// One completion, streamed to the sink when there is one
+async fn complete(
+    messages: Vec<Value>,
+    response_format: Option<Value>,
+    sink: Option<OutputSink<'_>>,
+    usage: &UsageMeter,
+) -> Result<String, AnalysisError> {
+    match sink {
+        Some(sink) => stream_openai_api(messages, response_format, sink, usage).await,
+        None => call_openai_api(messages, response_format, usage).await,
+    }
+}
+
+// Ask the model once, retrying with the validation error when structured output does not parse
+async fn call_model(
+    text: String,
+    system_message: &str,
+    format: Option<OutputFormat>,
+    sink: Option<OutputSink<'_>>,
+    usage: &UsageMeter,
+) -> Result<String, AnalysisError> {
+    let mut messages = vec![
+        json!({ "role": "system", "content": system_message }),
+        json!({ "role": "user", "content": text }),
+    ];
+    let Some(format) = format else {
+        return complete(messages, None, sink, usage).await;
+    };
+
+    let attempts = config_value("ANALYSIS_OUTPUT_ATTEMPTS", DEFAULT_OUTPUT_ATTEMPTS);
+    let mut last_error = String::new();
+    for attempt in 1..=attempts {
+        if attempt > 1 {
+            if let Some(sink) = sink {
+                if !sink(OutputEvent::Restart) {
+                    return Err(AnalysisError::Cancelled(String::new()));
+                }
+            }
+        }
+        let output = complete(
+            messages.clone(),
+            Some(format.response_format()),
+            sink,
+            usage,
+        )
+        .await?;
+        match (format.validate)(&output) {
+            Ok(()) => return Ok(output),
+            Err(e) => {
+                println!(
+                    "Invalid {} output on attempt {}/{}: {}",
+                    format.name, attempt, attempts, e
+                );
+                messages.push(json!({ "role": "assistant", "content": output }));
+                messages.push(json!({
+                    "role": "user",
+                    "content": format!(
+                        "That response was not valid: {}. Reply again with only JSON that matches the schema.",
+                        e
+                    )
+                }));
+                last_error = e;
+            }
+        }
+    }
+    Err(AnalysisError::InvalidOutput(last_error))
+}
+
+// A contiguous piece of the transcript, with its time range when segments are known
+struct Chunk {
+    start: Option<f64>,
+    end: Option<f64>,
+    text: String,
+}
+
+impl Chunk {
+    fn label(&self, index: usize, total: usize) -> String {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => format!(
+                "part {} of {} ({} to {})",
+                index + 1,
+                total,
+                format_timestamp(start),
+                format_timestamp(end)
+            ),
+            _ => format!("part {} of {}", index + 1, total),
+        }
+    }
+}
+
+// Break plain text after sentence-ending punctuation
+pub fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        current.push(c);
+        let at_boundary = matches!(c, '.' | '?' | '!')
+            && chars
+                .peek()
+                .map(|next| next.is_whitespace())
+                .unwrap_or(true);
+        if at_boundary {
+            sentences.push(current.trim().to_string());
+            current.clear();
+        }
+    }
+    if !current.trim().is_empty() {
+        sentences.push(current.trim().to_string());
+    }
+    sentences
+}
+
+// Split a unit that is too large on its own at word boundaries
+fn split_words(unit: Chunk, max_tokens: usize) -> Vec<Chunk> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    for word in unit.text.split_whitespace() {
+        if !current.is_empty() && estimate_tokens(&current) + estimate_tokens(word) > max_tokens {
+            pieces.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+        .into_iter()
+        .map(|text| Chunk {
+            start: unit.start,
+            end: unit.end,
+            text,
+        })
+        .collect()
+}
+
+// Group the units of the model input into parts of at most `max_tokens`: one line per segment
+// for timestamped input, speaker turns for diarized transcripts, otherwise sentences
+fn chunk_transcript(
+    input: &str,
+    segments: Option<&[TranscriptSegment]>,
+    timestamped: bool,
+    max_tokens: usize,
+) -> Vec<Chunk> {
+    let (units, separator): (Vec<Chunk>, &str) = match segments {
+        Some(segments) if timestamped => (
+            segments
+                .iter()
+                .filter(|segment| !segment.text.trim().is_empty())
+                .map(|segment| Chunk {
+                    start: Some(segment.start),
+                    end: Some(segment.end),
+                    text: format!(
+                        "[{}] {}",
+                        format_timestamp(segment.start),
+                        segment.attributed_text()
+                    ),
+                })
+                .collect(),
+            "\n",
+        ),
+        Some(segments) if has_speakers(segments) => (
+            speaker_turns(segments)
+                .into_iter()
+                .map(|turn| Chunk {
+                    start: Some(turn.start),
+                    end: Some(turn.end),
+                    text: format!("{}: {}", turn.speaker, turn.text),
+                })
+                .collect(),
+            "\n\n",
+        ),
+        _ => (
+            split_sentences(input)
+                .into_iter()
+                .map(|text| Chunk {
+                    start: None,
+                    end: None,
+                    text,
+                })
+                .collect(),
+            " ",
+        ),
+    };
+
+    let mut chunks: Vec<Chunk> = Vec::new();
+    for unit in units {
+        let pieces = if estimate_tokens(&unit.text) > max_tokens {
+            split_words(unit, max_tokens)
+        } else {
+            vec![unit]
+        };
+        for piece in pieces {
+            match chunks.last_mut() {
+                Some(last)
+                    if estimate_tokens(&last.text) + estimate_tokens(&piece.text) < max_tokens =>
+                {
+                    last.text.push_str(separator);
+                    last.text.push_str(&piece.text);
+                    last.end = piece.end.or(last.end);
+                }
+                _ => chunks.push(piece),
+            }
+        }
+    }
+    chunks
+}
+
+fn map_instructions(system_message: &str, label: &str) -> String {
+    format!(
+        "{}\n\nThe text below is {} of a longer transcription. Work only from this part; \
+         your output will be merged with the results for the other parts.",
+        system_message, label
+    )
+}
+
+fn reduce_instructions(system_message: &str) -> String {
+    format!(
+        "{}\n\nThe input consists of partial results, each produced from consecutive parts of \
+         the same transcription. Merge them into one coherent result in the requested format, \
+         removing duplicates and keeping chronological order.",
+        system_message
+    )
+}
+
+fn render_partials(partials: &[(String, String)]) -> String {
+    partials
+        .iter()
+        .map(|(label, result)| format!("### Result for {}\n{}", label, result))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+// Cut text to roughly `max_tokens`, on a character boundary
+fn truncate_to_tokens(text: &mut String, max_tokens: usize) {
+    let mut end = (max_tokens * 4).min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+}
+
+// Merge partial results, in rounds if they do not fit in a single request
+async fn reduce(
+    mut partials: Vec<(String, String)>,
+    system_message: &str,
+    format: Option<OutputFormat>,
+    max_input_tokens: usize,
+    concurrency: usize,
+    sink: Option<OutputSink<'_>>,
+    usage: &UsageMeter,
+) -> Result<String, AnalysisError> {
+    let instructions = reduce_instructions(system_message);
+    loop {
+        // Greedily pack consecutive partial results into batches that fit the input budget
+        let mut batches: Vec<Vec<(String, String)>> = Vec::new();
+        let mut batch_tokens = 0;
+        for partial in partials {
+            let tokens = estimate_tokens(&partial.1);
+            match batches.last_mut() {
+                Some(batch) if batch_tokens + tokens <= max_input_tokens => {
+                    batch_tokens += tokens;
+                    batch.push(partial);
+                }
+                _ => {
+                    batch_tokens = tokens;
+                    batches.push(vec![partial]);
+                }
+            }
+        }
+
+        // One batch left, or no batch could be combined further: do the final merge. Partial
+        // results that are too large to merge together are cut to an equal share of the budget
+        // so the request never exceeds it.
+        if batches.len() == 1 || batches.iter().all(|batch| batch.len() == 1) {
+            let mut all: Vec<(String, String)> = batches.into_iter().flatten().collect();
+            if estimate_tokens(&render_partials(&all)) > max_input_tokens {
+                let headers: Vec<(String, String)> = all
+                    .iter()
+                    .map(|(label, _)| (label.clone(), String::new()))
+                    .collect();
+                let share = max_input_tokens
+                    .saturating_sub(estimate_tokens(&render_partials(&headers)))
+                    / all.len();
+                println!(
+                    "{} partial results exceed the input budget; truncating each to ~{} tokens",
+                    all.len(),
+                    share
+                );
+                for (_, result) in all.iter_mut() {
+                    truncate_to_tokens(result, share);
+                }
+            }
+            return call_model(render_partials(&all), &instructions, format, sink, usage).await;
+        }
+
+        println!("Merging {} batches of partial results", batches.len());
+        let merged: Vec<Result<(String, String), AnalysisError>> = stream::iter(batches)
+            .map(|batch| {
+                let instructions = &instructions;
+                async move {
+                    if batch.len() == 1 {
+                        return Ok(batch.into_iter().next().unwrap());
+                    }
+                    let label = format!(
+                        "{} through {}",
+                        batch.first().unwrap().0,
+                        batch.last().unwrap().0
+                    );
+                    let result =
+                        call_model(render_partials(&batch), instructions, format, None, usage)
+                            .await?;
+                    Ok((label, result))
+                }
+            })
+            .buffered(concurrency)
+            .collect()
+            .await;
+        partials = merged.into_iter().collect::<Result<Vec<_>, _>>()?;
+    }
+}
+
+// Run an analysis over a transcript, splitting it into parts and merging the results
+// when it is too long to send in one request. Structured analyses see segment timestamps
+// so they can cite where in the recording each item came from.
+pub async fn run_analysis(request: AnalysisRequest<'_>) -> Result<String, AnalysisError> {
+    let AnalysisRequest {
+        transcript,
+        segments,
+        system_message,
+        format,
+        sink,
+        usage,
+    } = request;
+    let timestamped = format.is_some() && segments.is_some_and(|s| !s.is_empty());
+
+    let input = match segments {
+        Some(segments) if timestamped => timestamped_text(segments),
+        Some(segments) if has_speakers(segments) => turn_text(&speaker_turns(segments)),
+        _ => transcript,
+    };
+    let max_input_tokens = config_value("ANALYSIS_MAX_INPUT_TOKENS", DEFAULT_MAX_INPUT_TOKENS);
+    if estimate_tokens(&input) <= max_input_tokens {
+        return call_model(input, system_message, format, sink, usage).await;
+    }
+
+    let chunk_tokens = config_value("ANALYSIS_CHUNK_TOKENS", DEFAULT_CHUNK_TOKENS);
+    let concurrency = config_value("ANALYSIS_MAP_CONCURRENCY", DEFAULT_MAP_CONCURRENCY);
+    let chunks = chunk_transcript(&input, segments, timestamped, chunk_tokens);
+    let total = chunks.len();
+    println!(
+        "Transcript of ~{} tokens split into {} parts for analysis",
+        estimate_tokens(&input),
+        total
+    );
+
+    let partials: Vec<Result<(String, String), AnalysisError>> =
+        stream::iter(chunks.into_iter().enumerate())
+            .map(|(index, chunk)| async move {
+                let label = chunk.label(index, total);
+                let instructions = map_instructions(system_message, &label);
+                let result = call_model(chunk.text, &instructions, format, None, usage).await?;
+
+                // Only the final merge is streamed; report each part and stop if the listener left
+                if let Some(sink) = sink {
+                    if !sink(OutputEvent::Progress(&format!("Analysed {}", label))) {
+                        return Err(AnalysisError::Cancelled(String::new()));
+                    }
+                }
+                Ok((label, result))
+            })
+            .buffered(concurrency)
+            .collect()
+            .await;
+    let partials = partials.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    reduce(
+        partials,
+        system_message,
+        format,
+        max_input_tokens,
+        concurrency,
+        sink,
+        usage,
+    )
+    .await
+}
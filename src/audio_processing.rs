@@ -1,211 +1,327 @@
-use futures::future::join_all;
-use reqwest::{multipart, Client};
-use std::path::PathBuf;
-use std::process::Command;
-use std::sync::{Arc, Mutex};
-use tokio::fs::File;
-use tokio::task;
-use tokio_util::io::ReaderStream;
-pub async fn split_audio_by_size_and_transcribe(
-    input_path: &str,
-    max_segment_size: usize,
-    openai_api_key: &str,
-) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync + 'static>> {
-    let base_filename = PathBuf::from(input_path)
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .ok_or("Failed to extract file stem")?
-        .to_string();
-
-    let split_dir = PathBuf::from("split_audio");
-    if !split_dir.exists() {
-        std::fs::create_dir_all(&split_dir)?;
-    }
-
-    let output_extension = "mp3";
-    let segment_duration_secs = max_segment_size / (128000 / 8); // Assuming 128 kbps bitrate
-    let total_duration = get_audio_duration(input_path)?; // Assuming you have a function to get the total duration
-
-    let client = Arc::new(Client::new());
-    let transcriptions: Arc<Mutex<Vec<Option<String>>>> = Arc::new(Mutex::new(vec![
-        None;
-        total_segments(
-            total_duration,
-            segment_duration_secs
-        )
-    ]));
-
-    let mut all_tasks = vec![]; // To store all tasks (splitting + transcription)
-
-    // Split the audio file into segments and send each segment for transcription concurrently
-    for i in 0.. {
-        let start_time = i * segment_duration_secs;
-        if start_time >= total_duration {
-            break;
-        }
-
-        let segment_filename = format!("{}_part{}.{}", base_filename, i + 1, output_extension);
-        let output_path = split_dir.join(&segment_filename);
-
-        let client_clone = Arc::clone(&client);
-        let openai_api_key_clone = openai_api_key.to_string();
-        let transcriptions_clone = Arc::clone(&transcriptions);
-
-        let input_path = input_path.to_string();
-
-        // Spawn a task that splits the audio and immediately sends the segment for transcription
-        let task = task::spawn(async move {
-            // Step 1: Split the audio segment asynchronously
-            split_audio_segment(&input_path, start_time, segment_duration_secs, &output_path)?;
-
-            // Step 2: Immediately after splitting, send the segment for transcription
-            println!(
-                "Sending transcription request for file: {}",
-                output_path.display()
-            );
-
-            match transcribe_audio_segment(&client_clone, &openai_api_key_clone, &output_path).await
-            {
-                Ok(transcription) => {
-                    let mut transcriptions_lock = transcriptions_clone.lock().unwrap();
-                    transcriptions_lock[i] = Some(transcription);
-                    println!("Received transcription for file: {}", output_path.display());
-                }
-                Err(e) => {
-                    eprintln!("Error transcribing file {}: {:?}", output_path.display(), e);
-                }
-            }
-
-            Ok(()) as Result<(), Box<dyn std::error::Error + Send + Sync + 'static>>
-        });
-
-        // Collect the task handles
-        all_tasks.push(task);
-    }
-
-    // Wait for all tasks (splitting + transcribing) to complete
-    join_all(all_tasks).await;
-
-    // Lock the transcriptions and clone the data safely
-    let transcriptions_lock = transcriptions.lock().unwrap();
-    let final_transcriptions: Vec<String> = transcriptions_lock
-        .clone()
-        .into_iter()
-        .filter_map(|t| t)
-        .collect();
-
-    Ok(final_transcriptions)
-}
-
-// Split the audio file into segments
-fn split_audio_segment(
-    input_path: &str,
-    start_time: usize,
-    duration_secs: usize,
-    output_path: &PathBuf,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let status = Command::new("ffmpeg")
-        .arg("-i")
-        .arg(input_path)
-        .arg("-ss")
-        .arg(format!("{}", start_time))
-        .arg("-t")
-        .arg(format!("{}", duration_secs))
-        .arg(output_path.to_str().ok_or("Invalid output path")?)
-        .stdout(std::process::Stdio::null()) // Suppress stdout
-        .stderr(std::process::Stdio::null()) // Suppress stderr
-        .status()?;
-
-    if !status.success() {
-        return Err("ffmpeg command failed".into());
-    }
-
-    Ok(())
-}
-
-// Function to get the total duration of the audio file using ffmpeg
-fn get_audio_duration(input_path: &str) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
-    let output = Command::new("ffmpeg")
-        .arg("-i")
-        .arg(input_path)
-        .arg("-f")
-        .arg("null")
-        .arg("-")
-        .output()?;
-
-    let output_str = String::from_utf8_lossy(&output.stderr);
-    let duration_line = output_str
-        .lines()
-        .find(|line| line.contains("Duration"))
-        .ok_or("Duration not found")?;
-
-    let duration_str = duration_line.split_whitespace().nth(1).unwrap_or("");
-    let time_parts: Vec<&str> = duration_str.trim_end_matches(',').split(':').collect();
-
-    if time_parts.len() == 3 {
-        let hours: f64 = time_parts[0].parse()?;
-        let minutes: f64 = time_parts[1].parse()?;
-        let seconds: f64 = time_parts[2].parse()?;
-
-        let total_seconds = hours * 3600.0 + minutes * 60.0 + seconds;
-        return Ok(total_seconds as usize);
-    }
-
-    Err("Duration not found".into())
-}
-
-// Helper to calculate total segments based on duration and segment size
-fn total_segments(total_duration: usize, segment_duration_secs: usize) -> usize {
-    (total_duration + segment_duration_secs - 1) / segment_duration_secs // Rounds up to the nearest segment
-}
-
-// Function to send transcription request to OpenAI
-async fn transcribe_audio_segment(
-    client: &Client,
-    api_key: &str,
-    segment_path: &PathBuf,
-) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
-    let audio_file = segment_path.to_str().ok_or("Invalid path")?;
-    send_transcription_request(client, api_key, audio_file).await
-}
-
-async fn send_transcription_request(
-    client: &Client,
-    api_key: &str,
-    audio_file: &str,
-) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
-    let url = "https://api.openai.com/v1/audio/transcriptions";
-
-    // Open the file asynchronously
-    let file = File::open(audio_file).await?;
-
-    // Convert the file into a stream
-    let file_stream = ReaderStream::new(file);
-
-    // Create a Part from the stream
-    let part = multipart::Part::stream(reqwest::Body::wrap_stream(file_stream))
-        .file_name(audio_file.to_string())
-        .mime_str("audio/mpeg")?;
-
-    // Build the multipart form
-    let form = multipart::Form::new()
-        .text("model", "whisper-1")
-        .part("file", part);
-
-    // Send the request
-    let response = client
-        .post(url)
-        .bearer_auth(api_key)
-        .multipart(form)
-        .send()
-        .await?;
-
-    if response.status().is_success() {
-        let transcription: serde_json::Value = response.json().await?;
-        if let Some(transcription_text) = transcription["text"].as_str() {
-            return Ok(transcription_text.to_string());
-        }
-    }
-
-    Err("Failed to get transcription".into())
-}
+use crate::transcript::{self, TranscriptSegment};
+use crate::usage::{self, UsageMeter};
+use crate::vocabulary;
+use futures::future::join_all;
+use reqwest::{multipart, Client};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use tokio::fs::File;
+use tokio::task;
+use tokio_util::io::ReaderStream;
+
+// Speech-to-text model used for every segment
+pub const TRANSCRIPTION_MODEL: &str = "whisper-1";
+
+// Local scratch directory for the segments cut from an upload during transcription
+pub const SPLIT_DIR: &str = "split_audio";
+
+pub async fn split_audio_by_size_and_transcribe(
+    input_path: &str,
+    max_segment_size: usize,
+    openai_api_key: &str,
+    vocabulary: &[String],
+    usage: &Arc<UsageMeter>,
+) -> Result<Vec<TranscriptSegment>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let base_filename = PathBuf::from(input_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or("Failed to extract file stem")?
+        .to_string();
+
+    // Segments are plaintext copies of the upload, so each job cuts them into its own
+    // directory, which is removed with whatever is left in it however the job ends
+    let split_root = PathBuf::from(SPLIT_DIR);
+    if !split_root.exists() {
+        std::fs::create_dir_all(&split_root)?;
+    }
+    let split_dir = tempfile::Builder::new()
+        .prefix(&format!("{}-", base_filename))
+        .tempdir_in(&split_root)?;
+
+    let output_extension = "mp3";
+    let segment_duration_secs = max_segment_size / (128000 / 8); // Assuming 128 kbps bitrate
+    let total_duration = get_audio_duration(input_path)?; // Assuming you have a function to get the total duration
+
+    let client = Client::new();
+    let segment_count = total_segments(total_duration, segment_duration_secs);
+
+    // Cut one segment and transcribe it, with timestamps made relative to the upload
+    let transcribe_segment = |i: usize, prompt: Option<String>| {
+        let start_time = i * segment_duration_secs;
+        let segment_filename = format!("{}_part{}.{}", base_filename, i + 1, output_extension);
+        let output_path = split_dir.path().join(&segment_filename);
+        let input_path = input_path.to_string();
+        let client = client.clone();
+        let openai_api_key = openai_api_key.to_string();
+        let usage = Arc::clone(usage);
+
+        task::spawn(async move {
+            let split_path = output_path.clone();
+            task::spawn_blocking(move || {
+                split_audio_segment(&input_path, start_time, segment_duration_secs, &split_path)
+            })
+            .await?
+            .map_err(|e| format!("Error splitting audio: {:?}", e))?;
+
+            println!(
+                "Sending transcription request for file: {}",
+                output_path.display()
+            );
+            let transcribed = transcribe_audio_segment(
+                &client,
+                &openai_api_key,
+                &output_path,
+                prompt.as_deref(),
+                &usage,
+            )
+            .await;
+            if let Err(e) = tokio::fs::remove_file(&output_path).await {
+                eprintln!(
+                    "Failed to remove segment {}: {:?}",
+                    output_path.display(),
+                    e
+                );
+            }
+            let mut transcription = transcribed.map_err(|e| {
+                format!("Error transcribing file {}: {:?}", output_path.display(), e)
+            })?;
+            for segment in &mut transcription {
+                segment.start += start_time as f64;
+                segment.end += start_time as f64;
+            }
+            println!("Received transcription for file: {}", output_path.display());
+            Ok::<_, Box<dyn std::error::Error + Send + Sync + 'static>>(transcription)
+        })
+    };
+
+    // Segments are transcribed concurrently with the glossary as their prompt. Carrying the
+    // end of the previous segment's text into each prompt helps with words cut at a boundary,
+    // but makes every segment wait for the one before it, so it is opt-in.
+    let mut results = Vec::with_capacity(segment_count);
+    if carry_context() {
+        let mut previous_text: Option<String> = None;
+        for i in 0..segment_count {
+            let prompt = vocabulary::whisper_prompt(vocabulary, previous_text.as_deref());
+            let result = transcribe_segment(i, prompt).await?;
+            previous_text = result
+                .as_ref()
+                .ok()
+                .map(|transcription| transcript::plain_text(transcription));
+            results.push(result);
+        }
+    } else {
+        let prompt = vocabulary::whisper_prompt(vocabulary, None);
+        let tasks = (0..segment_count).map(|i| transcribe_segment(i, prompt.clone()));
+        for result in join_all(tasks).await {
+            results.push(result?);
+        }
+    }
+
+    let mut final_transcriptions = Vec::new();
+    for result in results {
+        match result {
+            Ok(transcription) => final_transcriptions.extend(transcription),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    Ok(final_transcriptions)
+}
+
+// Whether each segment's prompt carries the end of the previous segment's transcript
+fn carry_context() -> bool {
+    env::var("TRANSCRIPTION_CARRY_CONTEXT").is_ok_and(|value| value == "1" || value == "true")
+}
+
+// Split the audio file into segments
+fn split_audio_segment(
+    input_path: &str,
+    start_time: usize,
+    duration_secs: usize,
+    output_path: &Path,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let status = Command::new("ffmpeg")
+        .arg("-i")
+        .arg(input_path)
+        .arg("-ss")
+        .arg(format!("{}", start_time))
+        .arg("-t")
+        .arg(format!("{}", duration_secs))
+        .arg(output_path.to_str().ok_or("Invalid output path")?)
+        .stdout(std::process::Stdio::null()) // Suppress stdout
+        .stderr(std::process::Stdio::null()) // Suppress stderr
+        .status()?;
+
+    if !status.success() {
+        return Err("ffmpeg command failed".into());
+    }
+
+    Ok(())
+}
+
+// Browser playback copy of an upload: mono, loudness-normalised and at a low bitrate, so
+// reviewers can seek through long hearings on slow connections
+pub fn rendition_key(recording_id: &str) -> String {
+    format!("renditions/{}.mp3", recording_id)
+}
+
+const RENDITION_BITRATE: &str = "48k";
+const RENDITION_SAMPLE_RATE: &str = "22050";
+
+pub fn create_rendition(
+    input_path: &str,
+    output_path: &Path,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let status = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-i")
+        .arg(input_path)
+        .arg("-vn")
+        .arg("-ac")
+        .arg("1")
+        .arg("-ar")
+        .arg(RENDITION_SAMPLE_RATE)
+        .arg("-af")
+        .arg("loudnorm=I=-16:TP=-1.5:LRA=11")
+        .arg("-codec:a")
+        .arg("libmp3lame")
+        .arg("-b:a")
+        .arg(RENDITION_BITRATE)
+        .arg(output_path.to_str().ok_or("Invalid output path")?)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()?;
+
+    if !status.success() {
+        return Err("ffmpeg rendition failed".into());
+    }
+
+    Ok(())
+}
+
+// Function to get the total duration of the audio file using ffmpeg
+pub fn get_audio_duration(
+    input_path: &str,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let output = Command::new("ffmpeg")
+        .arg("-i")
+        .arg(input_path)
+        .arg("-f")
+        .arg("null")
+        .arg("-")
+        .output()?;
+
+    let output_str = String::from_utf8_lossy(&output.stderr);
+    let duration_line = output_str
+        .lines()
+        .find(|line| line.contains("Duration"))
+        .ok_or("Duration not found")?;
+
+    let duration_str = duration_line.split_whitespace().nth(1).unwrap_or("");
+    let time_parts: Vec<&str> = duration_str.trim_end_matches(',').split(':').collect();
+
+    if time_parts.len() == 3 {
+        let hours: f64 = time_parts[0].parse()?;
+        let minutes: f64 = time_parts[1].parse()?;
+        let seconds: f64 = time_parts[2].parse()?;
+
+        let total_seconds = hours * 3600.0 + minutes * 60.0 + seconds;
+        return Ok(total_seconds as usize);
+    }
+
+    Err("Duration not found".into())
+}
+
+// Helper to calculate total segments based on duration and segment size
+fn total_segments(total_duration: usize, segment_duration_secs: usize) -> usize {
+    total_duration.div_ceil(segment_duration_secs) // Rounds up to the nearest segment
+}
+
+// Function to send transcription request to OpenAI
+async fn transcribe_audio_segment(
+    client: &Client,
+    api_key: &str,
+    segment_path: &Path,
+    prompt: Option<&str>,
+    usage: &UsageMeter,
+) -> Result<Vec<TranscriptSegment>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let audio_file = segment_path.to_str().ok_or("Invalid path")?;
+    send_transcription_request(client, api_key, audio_file, prompt, usage).await
+}
+
+async fn send_transcription_request(
+    client: &Client,
+    api_key: &str,
+    audio_file: &str,
+    prompt: Option<&str>,
+    usage: &UsageMeter,
+) -> Result<Vec<TranscriptSegment>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let url = format!(
+        "{}/audio/transcriptions",
+        crate::analysis::openai_base_url()
+    );
+
+    // Open the file asynchronously
+    let file = File::open(audio_file).await?;
+
+    // Convert the file into a stream
+    let file_stream = ReaderStream::new(file);
+
+    // Create a Part from the stream
+    let part = multipart::Part::stream(reqwest::Body::wrap_stream(file_stream))
+        .file_name(audio_file.to_string())
+        .mime_str("audio/mpeg")?;
+
+    // Build the multipart form
+    let mut form = multipart::Form::new()
+        .text("model", TRANSCRIPTION_MODEL)
+        .text("response_format", "verbose_json")
+        .part("file", part);
+
+    // Vocabulary and preceding text bias the spelling of names and legal terms
+    if let Some(prompt) = prompt {
+        form = form.text("prompt", prompt.to_string());
+    }
+
+    // Send the request
+    let response = client
+        .post(&url)
+        .bearer_auth(api_key)
+        .multipart(form)
+        .send()
+        .await?;
+
+    if response.status().is_success() {
+        let transcription: serde_json::Value = response.json().await?;
+
+        // Whisper is billed by audio duration, which verbose_json reports
+        if let Some(duration) = transcription["duration"].as_f64() {
+            usage.add(usage::audio_usage(TRANSCRIPTION_MODEL, api_key, duration));
+        }
+
+        // Prefer the timestamped segments; fall back to the plain text as a single segment
+        if let Some(segments) = transcription["segments"].as_array() {
+            return Ok(segments
+                .iter()
+                .map(|segment| TranscriptSegment {
+                    start: segment["start"].as_f64().unwrap_or(0.0),
+                    end: segment["end"].as_f64().unwrap_or(0.0),
+                    text: segment["text"].as_str().unwrap_or("").trim().to_string(),
+                    speaker: None,
+                })
+                .collect());
+        }
+        if let Some(transcription_text) = transcription["text"].as_str() {
+            return Ok(vec![TranscriptSegment {
+                start: 0.0,
+                end: transcription["duration"].as_f64().unwrap_or(0.0),
+                text: transcription_text.to_string(),
+                speaker: None,
+            }]);
+        }
+    }
+
+    Err("Failed to get transcription".into())
+}
@@ -0,0 +1,340 @@
+use crate::transcript::TranscriptSegment;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+pub const MAX_RULES: usize = 500;
+pub const MAX_RULE_CHARS: usize = 200;
+
+// Compiled patterns are capped so a pathological regex cannot exhaust memory
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    Exact,
+    CaseInsensitive,
+    Regex,
+}
+
+impl MatchKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            MatchKind::Exact => "exact",
+            MatchKind::CaseInsensitive => "case_insensitive",
+            MatchKind::Regex => "regex",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MatchKind> {
+        match name {
+            "exact" => Some(MatchKind::Exact),
+            "case_insensitive" => Some(MatchKind::CaseInsensitive),
+            "regex" => Some(MatchKind::Regex),
+            _ => None,
+        }
+    }
+}
+
+// One entry of the correction dictionary, e.g. "Mr. Smyth" -> "Mr. Smith". Regex
+// replacements can refer to groups as `$1` or `${name}`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CorrectionRule {
+    #[serde(rename = "match")]
+    pub kind: MatchKind,
+    pub find: String,
+    pub replace: String,
+}
+
+// A replacement made in a transcript segment
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AppliedCorrection {
+    pub segment: usize,
+    pub start: f64,
+    // Position of the rule in the dictionary
+    pub rule: usize,
+    pub found: String,
+    pub replaced_with: String,
+}
+
+// What applying the dictionary to a transcript did, stored as `<id>.corrections.json`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CorrectionLog {
+    // Positions of rules that were not applied because they do not compile
+    pub skipped_rules: Vec<usize>,
+    pub corrections: Vec<AppliedCorrection>,
+}
+
+// Storage keys of the transcript before corrections and of the corrections made
+pub fn raw_segments_key(recording_id: &str) -> String {
+    format!("transcriptions/{}.raw.segments.json", recording_id)
+}
+
+pub fn corrections_key(recording_id: &str) -> String {
+    format!("transcriptions/{}.corrections.json", recording_id)
+}
+
+// Literal rules only match whole words at edges that are word characters, so "Smyth" is
+// not corrected inside "Smythe"
+fn literal_pattern(find: &str) -> String {
+    let boundary = |c: Option<char>| match c {
+        Some(c) if c.is_alphanumeric() || c == '_' => r"\b",
+        _ => "",
+    };
+    format!(
+        "{}{}{}",
+        boundary(find.chars().next()),
+        regex::escape(find),
+        boundary(find.chars().last())
+    )
+}
+
+fn compile(rule: &CorrectionRule) -> Result<Regex, String> {
+    let pattern = match rule.kind {
+        MatchKind::Regex => rule.find.clone(),
+        MatchKind::Exact | MatchKind::CaseInsensitive => literal_pattern(&rule.find),
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(rule.kind == MatchKind::CaseInsensitive)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| format!("Invalid pattern {:?}: {}", rule.find, e))
+}
+
+pub fn validate(rules: &[CorrectionRule]) -> Result<(), String> {
+    if rules.len() > MAX_RULES {
+        return Err(format!("At most {} rules are allowed", MAX_RULES));
+    }
+    for rule in rules {
+        if rule.find.is_empty()
+            || rule.find.chars().count() > MAX_RULE_CHARS
+            || rule.replace.chars().count() > MAX_RULE_CHARS
+        {
+            return Err(format!(
+                "find must be between 1 and {} characters, replace at most {}",
+                MAX_RULE_CHARS, MAX_RULE_CHARS
+            ));
+        }
+        compile(rule)?;
+    }
+    Ok(())
+}
+
+// The dictionary compiled for applying to transcripts
+pub struct Corrector {
+    // Each rule with its position in the dictionary
+    rules: Vec<(usize, CorrectionRule, Regex)>,
+    // Positions of rules that were skipped
+    skipped: Vec<usize>,
+}
+
+impl Corrector {
+    // Rules that do not compile are skipped; they are validated when saved
+    pub fn new(rules: &[CorrectionRule]) -> Corrector {
+        let mut compiled = Vec::new();
+        let mut skipped = Vec::new();
+        for (position, rule) in rules.iter().enumerate() {
+            match compile(rule) {
+                Ok(regex) => compiled.push((position, rule.clone(), regex)),
+                Err(e) => {
+                    eprintln!("Skipping correction rule {}: {}", position, e);
+                    skipped.push(position);
+                }
+            }
+        }
+        Corrector {
+            rules: compiled,
+            skipped,
+        }
+    }
+
+    pub fn skipped(&self) -> &[usize] {
+        &self.skipped
+    }
+
+    // Apply every rule in order to every segment, returning what was replaced
+    pub fn apply(&self, segments: &mut [TranscriptSegment]) -> Vec<AppliedCorrection> {
+        let mut applied = Vec::new();
+        for (index, segment) in segments.iter_mut().enumerate() {
+            for (position, rule, regex) in &self.rules {
+                let mut corrected = String::with_capacity(segment.text.len());
+                let mut last = 0;
+                for captures in regex.captures_iter(&segment.text) {
+                    let found = captures.get(0).expect("group 0 is the whole match");
+                    if found.is_empty() {
+                        continue;
+                    }
+                    let replacement = match rule.kind {
+                        MatchKind::Regex => {
+                            let mut expanded = String::new();
+                            captures.expand(&rule.replace, &mut expanded);
+                            expanded
+                        }
+                        MatchKind::Exact | MatchKind::CaseInsensitive => rule.replace.clone(),
+                    };
+                    if replacement == found.as_str() {
+                        continue;
+                    }
+                    corrected.push_str(&segment.text[last..found.start()]);
+                    corrected.push_str(&replacement);
+                    last = found.end();
+                    applied.push(AppliedCorrection {
+                        segment: index,
+                        start: segment.start,
+                        rule: *position,
+                        found: found.as_str().to_string(),
+                        replaced_with: replacement,
+                    });
+                }
+                // Every replacement consumes a non-empty match, so `last` moved if any was made
+                if last > 0 {
+                    corrected.push_str(&segment.text[last..]);
+                    segment.text = corrected;
+                }
+            }
+        }
+        applied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(kind: MatchKind, find: &str, replace: &str) -> CorrectionRule {
+        CorrectionRule {
+            kind,
+            find: find.to_string(),
+            replace: replace.to_string(),
+        }
+    }
+
+    fn segments(texts: &[&str]) -> Vec<TranscriptSegment> {
+        texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| TranscriptSegment {
+                start: i as f64 * 10.0,
+                end: i as f64 * 10.0 + 10.0,
+                text: text.to_string(),
+                speaker: None,
+            })
+            .collect()
+    }
+
+    fn correct(rules: &[CorrectionRule], texts: &[&str]) -> (Vec<String>, Vec<AppliedCorrection>) {
+        let mut segments = segments(texts);
+        let applied = Corrector::new(rules).apply(&mut segments);
+        (segments.into_iter().map(|s| s.text).collect(), applied)
+    }
+
+    #[test]
+    fn exact_rules_match_whole_words_with_case() {
+        let rules = [rule(MatchKind::Exact, "Smyth", "Smith")];
+        let (texts, applied) = correct(&rules, &["Mr Smyth and Ms Smythe", "smyth"]);
+        assert_eq!(texts, ["Mr Smith and Ms Smythe", "smyth"]);
+        assert_eq!(applied.len(), 1);
+        assert_eq!(
+            (applied[0].segment, applied[0].start, applied[0].rule),
+            (0, 0.0, 0)
+        );
+        assert_eq!(applied[0].found, "Smyth");
+        assert_eq!(applied[0].replaced_with, "Smith");
+    }
+
+    #[test]
+    fn case_insensitive_rules_record_what_was_found() {
+        let rules = [rule(
+            MatchKind::CaseInsensitive,
+            "habeus corpus",
+            "habeas corpus",
+        )];
+        let (texts, applied) = correct(&rules, &["A writ of Habeus Corpus and HABEUS CORPUS."]);
+        assert_eq!(texts, ["A writ of habeas corpus and habeas corpus."]);
+        let found: Vec<&str> = applied.iter().map(|a| a.found.as_str()).collect();
+        assert_eq!(found, ["Habeus Corpus", "HABEUS CORPUS"]);
+    }
+
+    #[test]
+    fn literal_rules_escape_punctuation() {
+        let rules = [rule(MatchKind::Exact, "Mr.", "Mr")];
+        let (texts, _) = correct(&rules, &["Mr. Jones, Mrs Jones, Mr? no"]);
+        assert_eq!(texts, ["Mr Jones, Mrs Jones, Mr? no"]);
+    }
+
+    #[test]
+    fn regex_rules_expand_groups() {
+        let rules = [rule(
+            MatchKind::Regex,
+            r"section (?P<number>\d+)\s*\((\w)\)",
+            "s.${number}($2)",
+        )];
+        let (texts, applied) = correct(&rules, &["under section 12 (b) and section 4(a)"]);
+        assert_eq!(texts, ["under s.12(b) and s.4(a)"]);
+        assert_eq!(applied[1].found, "section 4(a)");
+    }
+
+    #[test]
+    fn later_rules_see_earlier_replacements() {
+        let rules = [
+            rule(MatchKind::Exact, "Smyth", "Smith"),
+            rule(MatchKind::Exact, "Smith v Jonez", "Smith v Jones"),
+        ];
+        let (texts, applied) = correct(&rules, &["Smyth v Jonez"]);
+        assert_eq!(texts, ["Smith v Jones"]);
+        let rules_applied: Vec<usize> = applied.iter().map(|a| a.rule).collect();
+        assert_eq!(rules_applied, [0, 1]);
+    }
+
+    #[test]
+    fn overlapping_matches_are_replaced_once_left_to_right() {
+        let rules = [rule(MatchKind::Regex, "aa", "b")];
+        let (texts, applied) = correct(&rules, &["aaaaa"]);
+        assert_eq!(texts, ["bba"]);
+        assert_eq!(applied.len(), 2);
+
+        // A replacement containing its own pattern is not matched again
+        let rules = [rule(MatchKind::Exact, "Jones", "Jones KC")];
+        let (texts, applied) = correct(&rules, &["Jones for the Crown"]);
+        assert_eq!(texts, ["Jones KC for the Crown"]);
+        assert_eq!(applied.len(), 1);
+    }
+
+    #[test]
+    fn unchanged_and_empty_matches_are_not_recorded() {
+        let rules = [
+            rule(MatchKind::CaseInsensitive, "Court", "Court"),
+            rule(MatchKind::Regex, "x*", "y"),
+        ];
+        let (texts, applied) = correct(&rules, &["The Court rose"]);
+        assert_eq!(texts, ["The Court rose"]);
+        assert!(applied.is_empty());
+    }
+
+    #[test]
+    fn rules_that_do_not_compile_are_skipped_and_recorded() {
+        let rules = [
+            rule(MatchKind::Exact, "Smyth", "Smith"),
+            rule(MatchKind::Regex, "(", "x"),
+            rule(MatchKind::Exact, "Jonse", "Jones"),
+        ];
+        let corrector = Corrector::new(&rules);
+        assert_eq!(corrector.skipped(), [1]);
+        let mut segments = segments(&["Smyth and Jonse"]);
+        let applied = corrector.apply(&mut segments);
+        assert_eq!(segments[0].text, "Smith and Jones");
+        let positions: Vec<usize> = applied.iter().map(|c| c.rule).collect();
+        assert_eq!(positions, [0, 2]);
+    }
+
+    #[test]
+    fn validates_rules() {
+        assert!(validate(&[rule(MatchKind::Regex, "(", "x")]).is_err());
+        assert!(validate(&[rule(MatchKind::Exact, "", "x")]).is_err());
+        assert!(validate(&[rule(MatchKind::Exact, "a", &"x".repeat(MAX_RULE_CHARS + 1))]).is_err());
+        let many = vec![rule(MatchKind::Exact, "a", "b"); MAX_RULES + 1];
+        assert!(validate(&many).is_err());
+        // Literal rules are escaped, so regex syntax in them is fine
+        assert!(validate(&[rule(MatchKind::Exact, "(", "x")]).is_ok());
+    }
+}
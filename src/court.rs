@@ -0,0 +1,238 @@
+use crate::analysis::split_sentences;
+use crate::pdf::{self, PdfPage};
+use crate::transcript::{format_timestamp, TranscriptSegment};
+
+// Numbered lines on each page of a court transcript
+pub const LINES_PER_PAGE: usize = 25;
+
+// Characters of transcript text per line, and the indent that opens each speaker's turn
+const LINE_WIDTH: usize = 60;
+const TURN_INDENT: &str = "     ";
+
+// Sentences per paragraph when a transcript has no segments to follow
+const SENTENCES_PER_PARAGRAPH: usize = 4;
+
+// Printed at the top of every page
+pub struct Caption {
+    pub title: String,
+    pub date: String,
+}
+
+pub struct CourtLine {
+    // Shown in the margin where the recording time changes
+    pub timestamp: Option<String>,
+    pub text: String,
+}
+
+// A paragraph as words, each carrying the time of the segment it came from
+struct Paragraph {
+    words: Vec<(String, Option<f64>)>,
+}
+
+fn segment_words(segment: &TranscriptSegment) -> impl Iterator<Item = (String, Option<f64>)> + '_ {
+    segment
+        .text
+        .split_whitespace()
+        .map(|word| (word.to_string(), Some(segment.start)))
+}
+
+// Speaker turns become paragraphs opened by the speaker's name in capitals; without speakers
+// each segment is its own paragraph
+fn paragraphs(segments: Option<&[TranscriptSegment]>, transcript: &str) -> Vec<Paragraph> {
+    let Some(segments) = segments.filter(|s| !s.is_empty()) else {
+        return split_sentences(transcript)
+            .chunks(SENTENCES_PER_PARAGRAPH)
+            .map(|sentences| Paragraph {
+                words: sentences
+                    .join(" ")
+                    .split_whitespace()
+                    .map(|word| (word.to_string(), None))
+                    .collect(),
+            })
+            .collect();
+    };
+
+    let mut paragraphs: Vec<Paragraph> = Vec::new();
+    let mut current_speaker: Option<&str> = None;
+    for segment in segments.iter().filter(|s| !s.text.trim().is_empty()) {
+        match segment.speaker.as_deref() {
+            Some(speaker) if current_speaker == Some(speaker) => {
+                if let Some(paragraph) = paragraphs.last_mut() {
+                    paragraph.words.extend(segment_words(segment));
+                }
+            }
+            Some(speaker) => {
+                let mut words: Vec<(String, Option<f64>)> = speaker
+                    .to_uppercase()
+                    .split_whitespace()
+                    .map(|word| (word.to_string(), Some(segment.start)))
+                    .collect();
+                if let Some(last) = words.last_mut() {
+                    last.0.push(':');
+                }
+                words.extend(segment_words(segment));
+                paragraphs.push(Paragraph { words });
+            }
+            None => paragraphs.push(Paragraph {
+                words: segment_words(segment).collect(),
+            }),
+        }
+        current_speaker = segment.speaker.as_deref();
+    }
+    paragraphs
+}
+
+// Fill lines up to LINE_WIDTH characters, splitting words that are longer than a line
+fn wrap(paragraph: &Paragraph, lines: &mut Vec<CourtLine>, last_time: &mut Option<String>) {
+    let mut text = TURN_INDENT.to_string();
+    let mut time: Option<f64> = None;
+    let mut flush = |text: &mut String, time: &mut Option<f64>, lines: &mut Vec<CourtLine>| {
+        let stamp = time.take().map(format_timestamp);
+        let timestamp = if stamp.is_some() && stamp != *last_time {
+            *last_time = stamp.clone();
+            stamp
+        } else {
+            None
+        };
+        lines.push(CourtLine {
+            timestamp,
+            text: std::mem::take(text),
+        });
+    };
+
+    for (word, start) in &paragraph.words {
+        let mut word = word.as_str();
+        loop {
+            let separator = usize::from(!text.trim().is_empty());
+            let room = LINE_WIDTH.saturating_sub(text.chars().count() + separator);
+            if word.chars().count() <= room {
+                if separator == 1 {
+                    text.push(' ');
+                }
+                text.push_str(word);
+                time = time.or(*start);
+                break;
+            }
+            if !text.trim().is_empty() {
+                flush(&mut text, &mut time, lines);
+                continue;
+            }
+            // A word longer than a whole line
+            let split = word
+                .char_indices()
+                .nth(LINE_WIDTH - text.chars().count())
+                .map(|(i, _)| i)
+                .unwrap_or(word.len());
+            text.push_str(&word[..split]);
+            time = time.or(*start);
+            word = &word[split..];
+            flush(&mut text, &mut time, lines);
+            if word.is_empty() {
+                break;
+            }
+        }
+    }
+    if !text.trim().is_empty() {
+        flush(&mut text, &mut time, lines);
+    }
+}
+
+// Lay the transcript out as pages of LINES_PER_PAGE lines, padding the last page
+pub fn layout(segments: Option<&[TranscriptSegment]>, transcript: &str) -> Vec<Vec<CourtLine>> {
+    let mut lines = Vec::new();
+    let mut last_time = None;
+    for paragraph in paragraphs(segments, transcript) {
+        wrap(&paragraph, &mut lines, &mut last_time);
+    }
+
+    let mut pages: Vec<Vec<CourtLine>> = Vec::new();
+    for line in lines {
+        match pages.last_mut() {
+            Some(page) if page.len() < LINES_PER_PAGE => page.push(line),
+            _ => pages.push(vec![line]),
+        }
+    }
+    if pages.is_empty() {
+        pages.push(Vec::new());
+    }
+    if let Some(page) = pages.last_mut() {
+        while page.len() < LINES_PER_PAGE {
+            page.push(CourtLine {
+                timestamp: None,
+                text: String::new(),
+            });
+        }
+    }
+    pages
+}
+
+// Plain text, one page per form feed, with the time and line number in the left margin
+pub fn render_text(caption: &Caption, pages: &[Vec<CourtLine>]) -> String {
+    let total = pages.len();
+    let mut out = String::new();
+    for (index, page) in pages.iter().enumerate() {
+        if index > 0 {
+            out.push('\x0c');
+        }
+        let page_label = format!("Page {} of {}", index + 1, total);
+        let width = 8 + 2 + 2 + 2 + LINE_WIDTH;
+        let title_width = width.saturating_sub(page_label.len() + 1);
+        let title: String = caption.title.chars().take(title_width).collect();
+        out.push_str(&format!("{:<title_width$} {}\n", title, page_label));
+        out.push_str(&format!("{}\n\n", caption.date));
+        for (number, line) in page.iter().enumerate() {
+            let row = format!(
+                "{:<8}  {:>2}  {}",
+                line.timestamp.as_deref().unwrap_or(""),
+                number + 1,
+                line.text
+            );
+            out.push_str(row.trim_end());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+pub fn render_pdf(caption: &Caption, pages: &[Vec<CourtLine>]) -> Vec<u8> {
+    const MARGIN: f64 = 54.0;
+    const TEXT_SIZE: f64 = 11.0;
+    const LEADING: f64 = 26.0;
+    const TITLE_CHARS: usize = 48;
+    let number_x = MARGIN + 60.0;
+    let text_x = number_x + 3.0 * pdf::CHAR_WIDTH * TEXT_SIZE;
+    let top = pdf::PAGE_HEIGHT - 100.0;
+
+    let total = pages.len();
+    let rendered: Vec<PdfPage> = pages
+        .iter()
+        .enumerate()
+        .map(|(index, lines)| {
+            let mut page = PdfPage::default();
+            let page_label = format!("Page {} of {}", index + 1, total);
+            let label_width = page_label.len() as f64 * pdf::CHAR_WIDTH * 10.0;
+            let title: String = caption.title.chars().take(TITLE_CHARS).collect();
+            page.text(MARGIN, pdf::PAGE_HEIGHT - 54.0, 11.0, true, &title);
+            page.text(
+                pdf::PAGE_WIDTH - MARGIN - label_width,
+                pdf::PAGE_HEIGHT - 54.0,
+                10.0,
+                false,
+                &page_label,
+            );
+            page.text(MARGIN, pdf::PAGE_HEIGHT - 68.0, 10.0, false, &caption.date);
+            for (number, line) in lines.iter().enumerate() {
+                let y = top - number as f64 * LEADING;
+                if let Some(timestamp) = &line.timestamp {
+                    page.text(MARGIN, y, 8.0, false, timestamp);
+                }
+                page.text(number_x, y, TEXT_SIZE, false, &format!("{:>2}", number + 1));
+                if !line.text.is_empty() {
+                    page.text(text_x, y, TEXT_SIZE, false, &line.text);
+                }
+            }
+            page
+        })
+        .collect();
+    pdf::render(&caption.title, &rendered)
+}
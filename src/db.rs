@@ -0,0 +1,830 @@
+use crate::corrections::{CorrectionRule, MatchKind};
+use crate::deletion::DeletionAudit;
+use crate::recordings::{Artifact, Recording};
+use crate::revisions::Revision;
+use crate::speakers::Speaker;
+use crate::usage::UsageEntry;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, ToSql};
+use std::sync::Mutex;
+
+// Embedded metadata store for recordings and the artifacts derived from them
+pub struct Db {
+    conn: Mutex<Connection>,
+}
+
+// Criteria for listing recordings; `to` is exclusive and `cursor` is the last seen sequence number
+pub struct RecordingFilter {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub status: Option<String>,
+    pub query: Option<String>,
+    pub tags: Vec<String>,
+    pub cursor: Option<i64>,
+    pub limit: usize,
+}
+
+// How usage is broken down in reports
+#[derive(Clone, Copy)]
+pub enum UsageGrouping {
+    Recording,
+    ApiKey,
+    Model,
+    Operation,
+    Day,
+}
+
+impl UsageGrouping {
+    pub fn from_name(name: &str) -> Option<UsageGrouping> {
+        match name {
+            "recording" => Some(UsageGrouping::Recording),
+            "api_key" => Some(UsageGrouping::ApiKey),
+            "model" => Some(UsageGrouping::Model),
+            "operation" => Some(UsageGrouping::Operation),
+            "day" => Some(UsageGrouping::Day),
+            _ => None,
+        }
+    }
+
+    fn column(self) -> &'static str {
+        match self {
+            UsageGrouping::Recording => "COALESCE(recording_id, '')",
+            UsageGrouping::ApiKey => "api_key",
+            UsageGrouping::Model => "model",
+            UsageGrouping::Operation => "operation",
+            UsageGrouping::Day => "substr(created_at, 1, 10)",
+        }
+    }
+}
+
+pub struct UsageFilter {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub recording_id: Option<String>,
+    pub api_key: Option<String>,
+    pub group_by: UsageGrouping,
+}
+
+// Usage totals for one group and model
+pub struct UsageTotals {
+    pub group: String,
+    pub model: String,
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub audio_seconds: f64,
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS recordings (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        id TEXT NOT NULL UNIQUE,
+        title TEXT,
+        status TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        duration_secs INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS recordings_created_at ON recordings (created_at);
+    CREATE TABLE IF NOT EXISTS recording_tags (
+        recording_id TEXT NOT NULL REFERENCES recordings (id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (recording_id, tag)
+    );
+    CREATE TABLE IF NOT EXISTS artifacts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        recording_id TEXT NOT NULL REFERENCES recordings (id) ON DELETE CASCADE,
+        kind TEXT NOT NULL,
+        file TEXT NOT NULL,
+        model TEXT,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS artifacts_recording ON artifacts (recording_id);
+    CREATE TABLE IF NOT EXISTS deletion_audit (
+        id TEXT PRIMARY KEY,
+        recording_id TEXT NOT NULL,
+        deleted_at TEXT NOT NULL,
+        files TEXT NOT NULL,
+        artifact_rows INTEGER NOT NULL,
+        pending_purge TEXT NOT NULL DEFAULT '[]'
+    );
+";
+
+// Schema changes applied in order to existing databases; `PRAGMA user_version` records how
+// many have run. Only ever append to this list.
+const MIGRATIONS: [&str; 7] = [
+    "ALTER TABLE artifacts ADD COLUMN prompt_version TEXT",
+    "ALTER TABLE artifacts ADD COLUMN cache_key TEXT;
+     CREATE INDEX artifacts_cache_key ON artifacts (recording_id, kind, cache_key)",
+    // Usage is billing history, so it is kept when a recording is deleted
+    "CREATE TABLE usage_events (
+         id INTEGER PRIMARY KEY AUTOINCREMENT,
+         recording_id TEXT,
+         operation TEXT NOT NULL,
+         model TEXT NOT NULL,
+         api_key TEXT NOT NULL,
+         prompt_tokens INTEGER NOT NULL,
+         completion_tokens INTEGER NOT NULL,
+         audio_seconds REAL NOT NULL,
+         created_at TEXT NOT NULL
+     );
+     CREATE INDEX usage_events_created_at ON usage_events (created_at);
+     CREATE INDEX usage_events_recording ON usage_events (recording_id)",
+    "CREATE TABLE speakers (
+         recording_id TEXT NOT NULL REFERENCES recordings (id) ON DELETE CASCADE,
+         label TEXT NOT NULL,
+         name TEXT NOT NULL,
+         role TEXT,
+         PRIMARY KEY (recording_id, label)
+     )",
+    "ALTER TABLE artifacts ADD COLUMN transcript_revision INTEGER;
+     CREATE TABLE transcript_revisions (
+         recording_id TEXT NOT NULL REFERENCES recordings (id) ON DELETE CASCADE,
+         revision INTEGER NOT NULL,
+         author TEXT NOT NULL,
+         comment TEXT,
+         changed_segments INTEGER NOT NULL,
+         file TEXT NOT NULL,
+         created_at TEXT NOT NULL,
+         PRIMARY KEY (recording_id, revision)
+     )",
+    "CREATE TABLE vocabulary (
+         id INTEGER PRIMARY KEY AUTOINCREMENT,
+         term TEXT NOT NULL
+     )",
+    "CREATE TABLE correction_rules (
+         id INTEGER PRIMARY KEY AUTOINCREMENT,
+         kind TEXT NOT NULL,
+         find TEXT NOT NULL,
+         replacement TEXT NOT NULL
+     )",
+];
+
+// Timestamps are stored as fixed-width RFC 3339 strings so they sort lexicographically
+fn timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn parse_timestamp(value: String) -> rusqlite::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, e.into())
+        })
+}
+
+impl Db {
+    pub fn open(path: &str) -> rusqlite::Result<Db> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
+        conn.execute_batch(SCHEMA)?;
+        migrate(&conn)?;
+        Ok(Db {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn insert_recording(&self, recording: &Recording) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO recordings (id, title, status, created_at, duration_secs)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                recording.id,
+                recording.title,
+                recording.status,
+                timestamp(&recording.created_at),
+                recording.duration_secs.map(|d| d as i64),
+            ],
+        )?;
+        for tag in &recording.tags {
+            tx.execute(
+                "INSERT OR IGNORE INTO recording_tags (recording_id, tag) VALUES (?1, ?2)",
+                params![recording.id, tag],
+            )?;
+        }
+        for artifact in recording
+            .upload
+            .iter()
+            .chain(recording.transcript.iter())
+            .chain(recording.turns.iter())
+            .chain(recording.raw_transcript.iter())
+            .chain(recording.corrections.iter())
+            .chain(recording.rendition.iter())
+            .chain(recording.waveform.iter())
+            .chain(recording.analyses.iter())
+        {
+            insert_artifact(&tx, &recording.id, artifact)?;
+        }
+        tx.commit()
+    }
+
+    pub fn set_status(
+        &self,
+        id: &str,
+        status: &str,
+        duration_secs: Option<usize>,
+    ) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE recordings SET status = ?2, duration_secs = COALESCE(?3, duration_secs)
+             WHERE id = ?1",
+            params![id, status, duration_secs.map(|d| d as i64)],
+        )?;
+        Ok(())
+    }
+
+    // Record an artifact, replacing any earlier artifact of the same kind
+    pub fn put_artifact(&self, recording_id: &str, artifact: &Artifact) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM artifacts WHERE recording_id = ?1 AND kind = ?2",
+            params![recording_id, artifact.kind],
+        )?;
+        insert_artifact(&tx, recording_id, artifact)?;
+        tx.commit()
+    }
+
+    // Record an artifact alongside any others of the same kind
+    pub fn add_artifact(&self, recording_id: &str, artifact: &Artifact) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        insert_artifact(&conn, recording_id, artifact)
+    }
+
+    // The most recent artifact of a kind generated from the inputs identified by `cache_key`
+    pub fn find_cached_artifact(
+        &self,
+        recording_id: &str,
+        kind: &str,
+        cache_key: &str,
+    ) -> rusqlite::Result<Option<Artifact>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!(
+                "{} WHERE recording_id = ?1 AND kind = ?2 AND cache_key = ?3 ORDER BY id DESC LIMIT 1",
+                ARTIFACT_COLUMNS
+            ),
+            params![recording_id, kind, cache_key],
+            artifact_from_row,
+        )
+        .optional()
+    }
+
+    pub fn get_recording(&self, id: &str) -> rusqlite::Result<Option<Recording>> {
+        let conn = self.conn.lock().unwrap();
+        load_recording(&conn, id)
+    }
+
+    // Remove a recording and its artifact rows, recording the audit entry in the same transaction
+    pub fn delete_recording(&self, audit: &mut DeletionAudit) -> rusqlite::Result<bool> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        audit.artifact_rows = tx.query_row(
+            "SELECT COUNT(*) FROM artifacts WHERE recording_id = ?1",
+            params![audit.recording_id],
+            |row| row.get::<_, i64>(0),
+        )? as usize;
+        let deleted = tx.execute(
+            "DELETE FROM recordings WHERE id = ?1",
+            params![audit.recording_id],
+        )?;
+        if deleted == 0 {
+            return Ok(false);
+        }
+
+        let files = serde_json::to_string(&audit.files)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+        let pending_purge = serde_json::to_string(&audit.pending_purge)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+        tx.execute(
+            "INSERT INTO deletion_audit
+             (id, recording_id, deleted_at, files, artifact_rows, pending_purge)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                audit.id,
+                audit.recording_id,
+                timestamp(&audit.deleted_at),
+                files,
+                audit.artifact_rows as i64,
+                pending_purge,
+            ],
+        )?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    pub fn set_pending_purge(&self, audit_id: &str, files: &[String]) -> rusqlite::Result<()> {
+        let files = serde_json::to_string(files)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE deletion_audit SET pending_purge = ?2 WHERE id = ?1",
+            params![audit_id, files],
+        )?;
+        Ok(())
+    }
+
+    pub fn deletion_audit_exists(&self, audit_id: &str) -> rusqlite::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM deletion_audit WHERE id = ?1)",
+            params![audit_id],
+            |row| row.get(0),
+        )
+    }
+
+    // Deletions that still have staged files to purge, by audit id
+    pub fn pending_purges(&self) -> rusqlite::Result<Vec<(String, Vec<String>)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT id, pending_purge FROM deletion_audit WHERE pending_purge != '[]'")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter()
+            .map(|(id, files)| {
+                serde_json::from_str(&files)
+                    .map(|files| (id, files))
+                    .map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            1,
+                            rusqlite::types::Type::Text,
+                            e.into(),
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    pub fn record_usage(
+        &self,
+        recording_id: Option<&str>,
+        operation: &str,
+        entries: &[UsageEntry],
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let now = timestamp(&Utc::now());
+        for entry in entries {
+            tx.execute(
+                "INSERT INTO usage_events (recording_id, operation, model, api_key, prompt_tokens,
+                     completion_tokens, audio_seconds, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    recording_id,
+                    operation,
+                    entry.model,
+                    entry.api_key,
+                    entry.prompt_tokens as i64,
+                    entry.completion_tokens as i64,
+                    entry.audio_seconds,
+                    now,
+                ],
+            )?;
+        }
+        tx.commit()
+    }
+
+    // Names assigned to the recording's diarization labels
+    pub fn speakers(&self, recording_id: &str) -> rusqlite::Result<Vec<Speaker>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT label, name, role FROM speakers WHERE recording_id = ?1 ORDER BY label",
+        )?;
+        let speakers = stmt
+            .query_map(params![recording_id], |row| {
+                Ok(Speaker {
+                    label: row.get(0)?,
+                    name: row.get(1)?,
+                    role: row.get(2)?,
+                })
+            })?
+            .collect();
+        speakers
+    }
+
+    // Replace every name assigned for the recording
+    pub fn set_speakers(&self, recording_id: &str, speakers: &[Speaker]) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM speakers WHERE recording_id = ?1",
+            params![recording_id],
+        )?;
+        for speaker in speakers {
+            tx.execute(
+                "INSERT INTO speakers (recording_id, label, name, role) VALUES (?1, ?2, ?3, ?4)",
+                params![recording_id, speaker.label, speaker.name, speaker.role],
+            )?;
+        }
+        tx.commit()
+    }
+
+    // The organization's transcription vocabulary, in the order it was given
+    pub fn vocabulary(&self) -> rusqlite::Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT term FROM vocabulary ORDER BY id")?;
+        let terms = stmt.query_map([], |row| row.get(0))?.collect();
+        terms
+    }
+
+    pub fn set_vocabulary(&self, terms: &[String]) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM vocabulary", [])?;
+        for term in terms {
+            tx.execute("INSERT INTO vocabulary (term) VALUES (?1)", params![term])?;
+        }
+        tx.commit()
+    }
+
+    // The correction dictionary, in the order rules are applied
+    pub fn correction_rules(&self) -> rusqlite::Result<Vec<CorrectionRule>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT kind, find, replacement FROM correction_rules ORDER BY id")?;
+        let rules = stmt
+            .query_map([], |row| {
+                let kind: String = row.get(0)?;
+                Ok(CorrectionRule {
+                    kind: MatchKind::from_name(&kind).unwrap_or(MatchKind::Exact),
+                    find: row.get(1)?,
+                    replace: row.get(2)?,
+                })
+            })?
+            .collect();
+        rules
+    }
+
+    pub fn set_correction_rules(&self, rules: &[CorrectionRule]) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM correction_rules", [])?;
+        for rule in rules {
+            tx.execute(
+                "INSERT INTO correction_rules (kind, find, replacement) VALUES (?1, ?2, ?3)",
+                params![rule.kind.name(), rule.find, rule.replace],
+            )?;
+        }
+        tx.commit()
+    }
+
+    // Latest revision of the transcript; 1 until it is first edited
+    pub fn transcript_revision(&self, recording_id: &str) -> rusqlite::Result<u32> {
+        let conn = self.conn.lock().unwrap();
+        current_revision(&conn, recording_id)
+    }
+
+    // Stored revisions, oldest first; empty until the transcript is first edited
+    pub fn transcript_revisions(&self, recording_id: &str) -> rusqlite::Result<Vec<Revision>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT revision, author, comment, changed_segments, file, created_at
+             FROM transcript_revisions WHERE recording_id = ?1 ORDER BY revision",
+        )?;
+        let revisions = stmt
+            .query_map(params![recording_id], |row| {
+                Ok(Revision {
+                    revision: row.get(0)?,
+                    author: row.get(1)?,
+                    comment: row.get(2)?,
+                    changed_segments: row.get::<_, i64>(3)? as usize,
+                    file: row.get(4)?,
+                    created_at: parse_timestamp(row.get(5)?)?,
+                })
+            })?
+            .collect();
+        revisions
+    }
+
+    // Claim revision numbers before their segments are written. Fails with a constraint
+    // violation when a concurrent edit already took one of them.
+    pub fn add_revisions(
+        &self,
+        recording_id: &str,
+        revisions: &[Revision],
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for revision in revisions {
+            tx.execute(
+                "INSERT INTO transcript_revisions
+                     (recording_id, revision, author, comment, changed_segments, file, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    recording_id,
+                    revision.revision,
+                    revision.author,
+                    revision.comment,
+                    revision.changed_segments as i64,
+                    revision.file,
+                    timestamp(&revision.created_at),
+                ],
+            )?;
+        }
+        tx.commit()
+    }
+
+    // Release revisions whose segments could not be written
+    pub fn remove_revisions(&self, recording_id: &str, revisions: &[u32]) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        for revision in revisions {
+            conn.execute(
+                "DELETE FROM transcript_revisions WHERE recording_id = ?1 AND revision = ?2",
+                params![recording_id, revision],
+            )?;
+        }
+        Ok(())
+    }
+
+    // Usage summed per group and model; costs are applied by the caller since prices are per model
+    pub fn usage_totals(&self, filter: &UsageFilter) -> rusqlite::Result<Vec<UsageTotals>> {
+        let group = filter.group_by.column();
+        let mut sql = format!(
+            "SELECT {}, model, COUNT(*), SUM(prompt_tokens), SUM(completion_tokens),
+                 SUM(audio_seconds)
+             FROM usage_events WHERE 1 = 1",
+            group
+        );
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+        if let Some(from) = &filter.from {
+            sql.push_str(" AND created_at >= ?");
+            values.push(Box::new(timestamp(from)));
+        }
+        if let Some(to) = &filter.to {
+            sql.push_str(" AND created_at < ?");
+            values.push(Box::new(timestamp(to)));
+        }
+        if let Some(recording_id) = &filter.recording_id {
+            sql.push_str(" AND recording_id = ?");
+            values.push(Box::new(recording_id.clone()));
+        }
+        if let Some(api_key) = &filter.api_key {
+            sql.push_str(" AND api_key = ?");
+            values.push(Box::new(api_key.clone()));
+        }
+        sql.push_str(&format!(
+            " GROUP BY {}, model ORDER BY {}, model",
+            group, group
+        ));
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(values.iter()), |row| {
+                Ok(UsageTotals {
+                    group: row.get(0)?,
+                    model: row.get(1)?,
+                    calls: row.get::<_, i64>(2)? as u64,
+                    prompt_tokens: row.get::<_, i64>(3)? as u64,
+                    completion_tokens: row.get::<_, i64>(4)? as u64,
+                    audio_seconds: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    // List recordings newest first, returning the cursor for the next page if there is one
+    pub fn list_recordings(
+        &self,
+        filter: &RecordingFilter,
+    ) -> rusqlite::Result<(Vec<Recording>, Option<i64>)> {
+        let mut sql = String::from("SELECT seq, id FROM recordings WHERE 1 = 1");
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+
+        if let Some(from) = &filter.from {
+            sql.push_str(" AND created_at >= ?");
+            values.push(Box::new(timestamp(from)));
+        }
+        if let Some(to) = &filter.to {
+            sql.push_str(" AND created_at < ?");
+            values.push(Box::new(timestamp(to)));
+        }
+        if let Some(status) = &filter.status {
+            sql.push_str(" AND status = ?");
+            values.push(Box::new(status.clone()));
+        }
+        if let Some(query) = &filter.query {
+            sql.push_str(" AND title LIKE ? ESCAPE '\\'");
+            let escaped = query
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            values.push(Box::new(format!("%{}%", escaped)));
+        }
+        for tag in &filter.tags {
+            sql.push_str(" AND id IN (SELECT recording_id FROM recording_tags WHERE tag = ?)");
+            values.push(Box::new(tag.clone()));
+        }
+        if let Some(cursor) = filter.cursor {
+            sql.push_str(" AND seq < ?");
+            values.push(Box::new(cursor));
+        }
+        sql.push_str(" ORDER BY seq DESC LIMIT ?");
+        values.push(Box::new(filter.limit as i64 + 1));
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(values.iter()), |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let has_more = rows.len() > filter.limit;
+        let page = &rows[..rows.len().min(filter.limit)];
+        let next_cursor = if has_more {
+            page.last().map(|(seq, _)| *seq)
+        } else {
+            None
+        };
+
+        let mut recordings = Vec::with_capacity(page.len());
+        for (_, id) in page {
+            if let Some(recording) = load_recording(&conn, id)? {
+                recordings.push(recording);
+            }
+        }
+        Ok((recordings, next_cursor))
+    }
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let applied: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        conn.execute_batch(&format!(
+            "BEGIN; {}; PRAGMA user_version = {}; COMMIT;",
+            migration,
+            index + 1
+        ))?;
+    }
+    Ok(())
+}
+
+const ARTIFACT_COLUMNS: &str = "SELECT kind, file, model, prompt_version, cache_key, created_at,
+     transcript_revision FROM artifacts";
+
+fn artifact_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Artifact> {
+    Ok(Artifact {
+        kind: row.get(0)?,
+        file: row.get(1)?,
+        model: row.get(2)?,
+        prompt_version: row.get(3)?,
+        cache_key: row.get(4)?,
+        created_at: parse_timestamp(row.get(5)?)?,
+        transcript_revision: row.get(6)?,
+        stale: false,
+    })
+}
+
+fn insert_artifact(
+    conn: &Connection,
+    recording_id: &str,
+    artifact: &Artifact,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO artifacts (recording_id, kind, file, model, prompt_version, cache_key, created_at,
+             transcript_revision)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            recording_id,
+            artifact.kind,
+            artifact.file,
+            artifact.model,
+            artifact.prompt_version,
+            artifact.cache_key,
+            timestamp(&artifact.created_at),
+            artifact.transcript_revision,
+        ],
+    )?;
+    Ok(())
+}
+
+fn current_revision(conn: &Connection, recording_id: &str) -> rusqlite::Result<u32> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(revision), 1) FROM transcript_revisions WHERE recording_id = ?1",
+        params![recording_id],
+        |row| row.get(0),
+    )
+}
+
+fn load_recording(conn: &Connection, id: &str) -> rusqlite::Result<Option<Recording>> {
+    let recording = conn
+        .query_row(
+            "SELECT id, title, status, created_at, duration_secs FROM recordings WHERE id = ?1",
+            params![id],
+            |row| {
+                Ok(Recording {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    tags: Vec::new(),
+                    status: row.get(2)?,
+                    created_at: parse_timestamp(row.get(3)?)?,
+                    duration_secs: row.get::<_, Option<i64>>(4)?.map(|d| d as usize),
+                    upload: None,
+                    transcript: None,
+                    turns: None,
+                    raw_transcript: None,
+                    corrections: None,
+                    rendition: None,
+                    waveform: None,
+                    analyses: Vec::new(),
+                })
+            },
+        )
+        .optional()?;
+
+    let Some(mut recording) = recording else {
+        return Ok(None);
+    };
+
+    let mut stmt =
+        conn.prepare("SELECT tag FROM recording_tags WHERE recording_id = ?1 ORDER BY tag")?;
+    recording.tags = stmt
+        .query_map(params![id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "{} WHERE recording_id = ?1 ORDER BY id",
+        ARTIFACT_COLUMNS
+    ))?;
+    let artifacts = stmt
+        .query_map(params![id], artifact_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // Analyses of an earlier revision no longer match the transcript; older rows predate
+    // editing, so they were made from revision 1
+    let current = current_revision(conn, id)?;
+    for mut artifact in artifacts {
+        match artifact.kind.as_str() {
+            "upload" => recording.upload = Some(artifact),
+            "transcript" => recording.transcript = Some(artifact),
+            "turns" => recording.turns = Some(artifact),
+            "raw_transcript" => recording.raw_transcript = Some(artifact),
+            "corrections" => recording.corrections = Some(artifact),
+            "rendition" => recording.rendition = Some(artifact),
+            "waveform" => recording.waveform = Some(artifact),
+            _ => {
+                artifact.stale = artifact.transcript_revision.unwrap_or(1) < current;
+                recording.analyses.push(artifact)
+            }
+        }
+    }
+    Ok(Some(recording))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn revision(number: u32) -> Revision {
+        Revision {
+            revision: number,
+            author: "clerk".to_string(),
+            comment: None,
+            changed_segments: 1,
+            file: format!("transcriptions/r{}.segments.json", number),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn only_analyses_of_earlier_revisions_are_stale() {
+        let db = Db::open(":memory:").unwrap();
+        let id = "11111111-1111-1111-1111-111111111111";
+        db.insert_recording(&Recording::new(id)).unwrap();
+        for kind in ["upload", "transcript", "turns", "rendition", "waveform"] {
+            let artifact = Artifact::new(kind, &format!("{}/{}", kind, id), None);
+            db.put_artifact(id, &artifact).unwrap();
+        }
+        let mut summary = Artifact::new("summary", "summaries/old.txt", None);
+        summary.transcript_revision = Some(1);
+        db.add_artifact(id, &summary).unwrap();
+
+        db.add_revisions(id, &[revision(1), revision(2)]).unwrap();
+        let mut key_points = Artifact::new("key_points", "key_points/new.json", None);
+        key_points.transcript_revision = Some(2);
+        db.add_artifact(id, &key_points).unwrap();
+
+        let recording = db.get_recording(id).unwrap().unwrap();
+        for artifact in [
+            &recording.upload,
+            &recording.transcript,
+            &recording.turns,
+            &recording.rendition,
+            &recording.waveform,
+        ] {
+            assert!(!artifact.as_ref().unwrap().stale);
+        }
+        let stale: Vec<(&str, bool)> = recording
+            .analyses
+            .iter()
+            .map(|a| (a.kind.as_str(), a.stale))
+            .collect();
+        assert_eq!(stale, [("summary", true), ("key_points", false)]);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::audio_processing::SPLIT_DIR;
+use crate::db::Db;
+use crate::recordings::Recording;
+use crate::storage::{Storage, ARTIFACT_DIRS};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::fs;
+use uuid::Uuid;
+
+// Deleted objects are moved under this prefix first so a failure part way through can be rolled back
+const TRASH_PREFIX: &str = ".trash";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DeletionAudit {
+    pub id: String,
+    pub recording_id: String,
+    pub deleted_at: DateTime<Utc>,
+    pub files: Vec<String>,
+    pub artifact_rows: usize,
+    // Staged copies that are not removed yet; the purge is retried at startup
+    pub pending_purge: Vec<String>,
+}
+
+// Every stored object that belongs to the recording
+async fn recording_files(
+    storage: &dyn Storage,
+    recording: &Recording,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    // Files written before the recording was tracked, or by older versions of the server
+    let mut candidates = Vec::new();
+    for dir in ARTIFACT_DIRS {
+        candidates.extend(storage.list(&format!("{}/{}", dir, recording.id)).await?);
+    }
+
+    let tracked = recording
+        .upload
+        .iter()
+        .chain(recording.transcript.iter())
+        .chain(recording.turns.iter())
+        .chain(recording.raw_transcript.iter())
+        .chain(recording.corrections.iter())
+        .chain(recording.rendition.iter())
+        .chain(recording.waveform.iter())
+        .chain(recording.analyses.iter());
+    for artifact in tracked {
+        if !candidates.contains(&artifact.file) {
+            candidates.extend(storage.list(&artifact.file).await?);
+        }
+    }
+
+    candidates.sort();
+    candidates.dedup();
+    Ok(candidates)
+}
+
+// Local copies of the audio cut for transcription: the per-job segment directories, which
+// only outlive a job if the server stopped during it, and the loose segments written by
+// versions that did not clean up after transcribing
+async fn segment_files(recording_id: &str) -> Result<Vec<String>, std::io::Error> {
+    let mut files = Vec::new();
+    let job_prefix = format!("{}-", recording_id);
+    let legacy_prefix = format!("{}_part", recording_id);
+    let mut entries = match fs::read_dir(SPLIT_DIR).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(e),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with(&job_prefix) || name.starts_with(&legacy_prefix) {
+            files.push(format!("{}/{}", SPLIT_DIR, name));
+        }
+    }
+    Ok(files)
+}
+
+// Move staged objects back to where they came from
+async fn restore(storage: &dyn Storage, staging: &str, moved: &[String]) {
+    for file in moved {
+        if let Err(e) = storage.rename(&format!("{}/{}", staging, file), file).await {
+            eprintln!("Failed to restore {} after aborted deletion: {:?}", file, e);
+        }
+    }
+}
+
+// Delete a recording and every derived artifact; either everything goes or nothing does
+pub async fn delete_recording(
+    db: &Db,
+    storage: &dyn Storage,
+    recording: &Recording,
+) -> Result<DeletionAudit, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let stored = recording_files(storage, recording).await?;
+    let segments = segment_files(&recording.id).await?;
+    let mut audit = DeletionAudit {
+        id: Uuid::new_v4().to_string(),
+        recording_id: recording.id.clone(),
+        deleted_at: Utc::now(),
+        files: stored.iter().chain(segments.iter()).cloned().collect(),
+        artifact_rows: 0,
+        pending_purge: Vec::new(),
+    };
+
+    let staging = format!("{}/{}", TRASH_PREFIX, audit.id);
+    let mut moved = Vec::new();
+    for file in &stored {
+        if let Err(e) = storage.rename(file, &format!("{}/{}", staging, file)).await {
+            restore(storage, &staging, &moved).await;
+            return Err(format!("Failed to remove {}: {}", file, e).into());
+        }
+        moved.push(file.clone());
+    }
+
+    // The staged copies are listed in the audit row itself, so they are purged eventually
+    // even if the server stops before the loop below finishes
+    audit.pending_purge = moved.clone();
+    match db.delete_recording(&mut audit) {
+        Ok(true) => {}
+        Ok(false) => {
+            restore(storage, &staging, &moved).await;
+            return Err("Recording was already deleted".into());
+        }
+        Err(e) => {
+            restore(storage, &staging, &moved).await;
+            return Err(Box::new(e));
+        }
+    }
+
+    // The database no longer references the objects, so dropping the staged copies is final
+    audit.pending_purge = purge(db, storage, &audit.id, &moved).await;
+    for segment in &segments {
+        let removed = match fs::metadata(segment).await {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(segment).await,
+            _ => fs::remove_file(segment).await,
+        };
+        if let Err(e) = removed {
+            eprintln!("Failed to remove segment {}: {:?}", segment, e);
+        }
+    }
+
+    println!(
+        "Deleted recording {} ({} files, {} artifact rows)",
+        audit.recording_id,
+        audit.files.len(),
+        audit.artifact_rows
+    );
+    Ok(audit)
+}
+
+// Delete staged files one by one, taking each off the audit row's pending list once it is
+// gone; returns the files that are still pending
+async fn purge(db: &Db, storage: &dyn Storage, audit_id: &str, files: &[String]) -> Vec<String> {
+    let staging = format!("{}/{}", TRASH_PREFIX, audit_id);
+    let mut remaining = files.to_vec();
+    for file in files {
+        if let Err(e) = storage.delete(&format!("{}/{}", staging, file)).await {
+            eprintln!("Failed to purge staged file {}: {:?}", file, e);
+            continue;
+        }
+        remaining.retain(|pending| pending != file);
+        if let Err(e) = db.set_pending_purge(audit_id, &remaining) {
+            eprintln!("Failed to record pending purge for {}: {:?}", audit_id, e);
+        }
+    }
+    remaining
+}
+
+// Retry purging staged files left behind by earlier deletions, returning how many went
+pub async fn resume_purges(db: &Db, storage: &dyn Storage) -> rusqlite::Result<usize> {
+    let mut purged = 0;
+    for (audit_id, files) in db.pending_purges()? {
+        let remaining = purge(db, storage, &audit_id, &files).await;
+        purged += files.len() - remaining.len();
+    }
+    Ok(purged)
+}
+
+// Move back files staged by deletions that never reached the database, e.g. because the
+// server stopped in between; returns how many were restored
+pub async fn restore_abandoned(
+    db: &Db,
+    storage: &dyn Storage,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let mut restored = 0;
+    let mut staged: Vec<(String, String)> = Vec::new();
+    for key in storage.list(&format!("{}/", TRASH_PREFIX)).await? {
+        let Some((audit_id, file)) = key
+            .strip_prefix(&format!("{}/", TRASH_PREFIX))
+            .and_then(|rest| rest.split_once('/'))
+        else {
+            continue;
+        };
+        staged.push((audit_id.to_string(), file.to_string()));
+    }
+    for (audit_id, file) in staged {
+        if db.deletion_audit_exists(&audit_id)? {
+            continue;
+        }
+        let key = format!("{}/{}/{}", TRASH_PREFIX, audit_id, file);
+        match storage.rename(&key, &file).await {
+            Ok(()) => restored += 1,
+            Err(e) => eprintln!("Failed to restore {} from {}: {:?}", file, key, e),
+        }
+    }
+    Ok(restored)
+}
@@ -0,0 +1,100 @@
+use crate::transcript::TranscriptSegment;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::process::Command;
+
+// A stretch of audio attributed to one speaker by the diarization tool
+#[derive(Deserialize, Clone, Debug)]
+pub struct SpeakerSpan {
+    pub start: f64,
+    pub end: f64,
+    pub speaker: String,
+}
+
+// Run the diarization tool configured with DIARIZATION_COMMAND on an audio file. The command
+// is split on whitespace and the audio path appended as its last argument; it must print
+// either a JSON array of `{"start", "end", "speaker"}` objects or RTTM lines (as written by
+// pyannote). Returns None when no tool is configured.
+pub fn diarize(audio_path: &str) -> Result<Option<Vec<SpeakerSpan>>, String> {
+    let Ok(command) = env::var("DIARIZATION_COMMAND") else {
+        return Ok(None);
+    };
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        return Ok(None);
+    };
+
+    let output = Command::new(program)
+        .args(parts)
+        .arg(audio_path)
+        .stderr(std::process::Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!("{} exited with {}", program, output.status));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let spans = match serde_json::from_str::<Vec<SpeakerSpan>>(stdout.trim()) {
+        Ok(spans) => spans,
+        Err(_) => parse_rttm(&stdout)?,
+    };
+    Ok(Some(spans))
+}
+
+// RTTM: `SPEAKER <file> <channel> <start> <duration> <NA> <NA> <speaker> <NA> <NA>`
+fn parse_rttm(output: &str) -> Result<Vec<SpeakerSpan>, String> {
+    let mut spans = Vec::new();
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 8 || fields[0] != "SPEAKER" {
+            return Err(format!("Unrecognised diarization output: {}", line));
+        }
+        let start: f64 = fields[3]
+            .parse()
+            .map_err(|_| format!("Invalid start time in: {}", line))?;
+        let duration: f64 = fields[4]
+            .parse()
+            .map_err(|_| format!("Invalid duration in: {}", line))?;
+        spans.push(SpeakerSpan {
+            start,
+            end: start + duration,
+            speaker: fields[7].to_string(),
+        });
+    }
+    Ok(spans)
+}
+
+// Label each segment with the speaker whose spans overlap it the most. Tool labels such as
+// SPEAKER_00 are renamed "Speaker 1", "Speaker 2", ... in order of first appearance.
+pub fn assign_speakers(segments: &mut [TranscriptSegment], spans: &[SpeakerSpan]) {
+    let mut spans = spans.to_vec();
+    spans.sort_by(|a, b| a.start.total_cmp(&b.start));
+    let mut labels: HashMap<String, String> = HashMap::new();
+    for span in &spans {
+        let next = format!("Speaker {}", labels.len() + 1);
+        labels.entry(span.speaker.clone()).or_insert(next);
+    }
+
+    for segment in segments.iter_mut() {
+        let mut overlap: Vec<(&str, f64)> = Vec::new();
+        for span in &spans {
+            let shared = segment.end.min(span.end) - segment.start.max(span.start);
+            if shared <= 0.0 {
+                continue;
+            }
+            match overlap
+                .iter_mut()
+                .find(|(speaker, _)| *speaker == span.speaker)
+            {
+                Some((_, total)) => *total += shared,
+                None => overlap.push((span.speaker.as_str(), shared)),
+            }
+        }
+        segment.speaker = overlap
+            .into_iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(speaker, _)| labels[speaker].clone());
+    }
+}
@@ -0,0 +1,311 @@
+use crate::storage::{empty_stream, Storage, StorageError};
+use actix_web::body::SizedStream;
+use actix_web::http::header::{self, HttpDate};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{HttpRequest, HttpResponse};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// MIME type of a stored object, from its extension
+pub fn content_type(key: &str) -> &'static str {
+    let extension = key
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "wav" => "audio/wav",
+        "ogg" | "opus" => "audio/ogg",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "html" => "text/html; charset=utf-8",
+        "pdf" => "application/pdf",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        _ => "application/octet-stream",
+    }
+}
+
+// Content-Disposition per RFC 6266: a quoted ASCII fallback name, plus the exact name
+// percent-encoded in `filename*` (RFC 8187) when it has characters the fallback cannot carry
+pub fn content_disposition(inline: bool, filename: &str) -> String {
+    let kind = if inline { "inline" } else { "attachment" };
+    let fallback: String = filename
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if fallback == filename {
+        return format!("{}; filename=\"{}\"", kind, fallback);
+    }
+    let encoded: String = filename
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect();
+    format!(
+        "{}; filename=\"{}\"; filename*=UTF-8''{}",
+        kind, fallback, encoded
+    )
+}
+
+// Validator derived from the object's size and modification time, so it can be computed
+// without reading the object. None when the backend does not report modification times.
+fn etag(size: u64, modified: Option<SystemTime>) -> Option<String> {
+    let since_epoch = modified?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("\"{:x}-{:x}\"", size, since_epoch.as_nanos()))
+}
+
+// `If-None-Match` lists ETags, possibly weak (`W/"..."`), or is `*`
+fn etag_matches(header: &str, etag: &str) -> bool {
+    header
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+fn whole_seconds(time: SystemTime) -> Duration {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Duration::from_secs(since_epoch.as_secs())
+}
+
+// HTTP dates have one-second resolution, so compare at that precision
+fn not_modified_since(modified: SystemTime, since: &str) -> bool {
+    match since.parse::<HttpDate>() {
+        Ok(since) => whole_seconds(modified) <= whole_seconds(SystemTime::from(since)),
+        Err(_) => false,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Full,
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+// A single `bytes=` range; anything else (including several ranges) is served in full,
+// which RFC 9110 allows
+fn parse_range(header: &str, length: u64) -> ByteRange {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+    let range = if start.is_empty() {
+        // Suffix range: the last `end` bytes
+        match end.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(suffix) => (length.saturating_sub(suffix), length.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        }
+    } else {
+        let Ok(start) = start.parse::<u64>() else {
+            return ByteRange::Full;
+        };
+        let end = match end {
+            "" => length.saturating_sub(1),
+            end => match end.parse::<u64>() {
+                Ok(end) if end >= start => end.min(length.saturating_sub(1)),
+                _ => return ByteRange::Full,
+            },
+        };
+        (start, end)
+    };
+    if length == 0 || range.0 >= length {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial(range.0, range.1)
+}
+
+fn header_value(request: &HttpRequest, name: header::HeaderName) -> Option<&str> {
+    request
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+}
+
+// Stream a stored object, honouring conditional requests (If-None-Match, If-Modified-Since)
+// and byte ranges (Range, If-Range) so players can seek without downloading everything
+pub async fn serve(
+    request: &HttpRequest,
+    storage: &dyn Storage,
+    key: &str,
+    content_type: &str,
+    disposition: Option<String>,
+) -> HttpResponse {
+    let size = match storage.size(key).await {
+        Ok(size) => size,
+        Err(StorageError::NotFound(_)) | Err(StorageError::InvalidKey(_)) => {
+            return HttpResponse::NotFound().body("File not found")
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().body(format!("Error reading file: {}", e))
+        }
+    };
+    let modified = match storage.modified(key).await {
+        Ok(modified) => modified,
+        Err(e) => {
+            println!("Could not read modification time of {}: {:?}", key, e);
+            None
+        }
+    };
+    let etag = etag(size, modified);
+
+    let not_modified = match (header_value(request, header::IF_NONE_MATCH), &etag) {
+        (Some(tags), Some(etag)) => etag_matches(tags, etag),
+        (Some(_), None) => false,
+        (None, _) => match (modified, header_value(request, header::IF_MODIFIED_SINCE)) {
+            (Some(modified), Some(since)) => not_modified_since(modified, since),
+            _ => false,
+        },
+    };
+
+    let mut response = HttpResponse::build(StatusCode::OK);
+    response.insert_header((header::ACCEPT_RANGES, "bytes"));
+    if let Some(etag) = &etag {
+        response.insert_header((header::ETAG, etag.clone()));
+    }
+    if let Some(modified) = modified {
+        response.insert_header((header::LAST_MODIFIED, HttpDate::from(modified).to_string()));
+    }
+    if not_modified {
+        return response.status(StatusCode::NOT_MODIFIED).finish();
+    }
+    response.content_type(content_type.to_string());
+    if let Some(disposition) = disposition {
+        response.insert_header((header::CONTENT_DISPOSITION, disposition));
+    }
+
+    // A range only applies while the representation still matches If-Range
+    let range_current = match header_value(request, header::IF_RANGE) {
+        Some(if_range) if if_range.trim_start().starts_with('"') => {
+            etag.as_deref() == Some(if_range.trim())
+        }
+        Some(if_range) => modified.is_some_and(|m| not_modified_since(m, if_range)),
+        None => true,
+    };
+    let range = match header_value(request, header::RANGE) {
+        Some(range) if range_current => parse_range(range, size),
+        _ => ByteRange::Full,
+    };
+    let (offset, length) = match range {
+        ByteRange::Full => (0, size),
+        ByteRange::Partial(start, end) => {
+            response.status(StatusCode::PARTIAL_CONTENT).insert_header((
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, size),
+            ));
+            (start, end - start + 1)
+        }
+        ByteRange::Unsatisfiable => {
+            return response
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .insert_header((header::CONTENT_RANGE, format!("bytes */{}", size)))
+                .finish()
+        }
+    };
+
+    // HEAD only needs the headers, so the object is not read
+    if request.method() == Method::HEAD {
+        return response.body(SizedStream::new(length, empty_stream()));
+    }
+    match storage.get_range(key, offset, length).await {
+        Ok(stream) => response.body(SizedStream::new(length, stream)),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Error reading file: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), ByteRange::Partial(0, 99));
+        assert_eq!(
+            parse_range("bytes= 10 - 20 ", 1000),
+            ByteRange::Partial(10, 20)
+        );
+        // The end is clamped to the last byte
+        assert_eq!(
+            parse_range("bytes=990-2000", 1000),
+            ByteRange::Partial(990, 999)
+        );
+        assert_eq!(
+            parse_range("bytes=500-", 1000),
+            ByteRange::Partial(500, 999)
+        );
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(
+            parse_range("bytes=-100", 1000),
+            ByteRange::Partial(900, 999)
+        );
+        assert_eq!(parse_range("bytes=-5000", 1000), ByteRange::Partial(0, 999));
+        assert_eq!(parse_range("bytes=-0", 1000), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn rejects_ranges_past_the_end() {
+        assert_eq!(parse_range("bytes=1000-", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(
+            parse_range("bytes=1000-1100", 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(parse_range("bytes=0-", 0), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-10", 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn serves_other_ranges_in_full() {
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), ByteRange::Full);
+        assert_eq!(parse_range("items=0-1", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=5-1", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=a-b", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=10", 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn keeps_ascii_filenames_as_they_are() {
+        assert_eq!(
+            content_disposition(true, "summary.txt"),
+            "inline; filename=\"summary.txt\""
+        );
+        assert_eq!(
+            content_disposition(false, "Hearing 12 (day 2).mp3"),
+            "attachment; filename=\"Hearing 12 (day 2).mp3\""
+        );
+    }
+
+    #[test]
+    fn encodes_non_ascii_filenames() {
+        assert_eq!(
+            content_disposition(false, "Anhörung – Müller.pdf"),
+            "attachment; filename=\"Anh_rung _ M_ller.pdf\"; \
+             filename*=UTF-8''Anh%C3%B6rung%20%E2%80%93%20M%C3%BCller.pdf"
+        );
+        // Quotes and backslashes cannot appear in the quoted fallback
+        assert_eq!(
+            content_disposition(true, "a\"b\\c.txt"),
+            "inline; filename=\"a_b_c.txt\"; filename*=UTF-8''a%22b%5Cc.txt"
+        );
+    }
+}
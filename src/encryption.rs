@@ -0,0 +1,665 @@
+use crate::storage::{
+    collect_stream, copy_to_file, empty_stream, ByteStream, Storage, StorageError, StorageResult,
+    ARTIFACT_DIRS,
+};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+// Envelope format: every object gets its own random data key, which is wrapped with the
+// configured master key. The payload is split into fixed-size chunks, each sealed with
+// AES-256-GCM under a nonce built from a per-object prefix, the chunk counter and a
+// last-chunk flag, so large files can be encrypted and decrypted without buffering them.
+//
+// magic (4) | key id (8) | wrap nonce (12) | wrapped data key (48) | nonce prefix (7) | chunk size (4)
+const MAGIC: &[u8; 4] = b"HSE1";
+const KEY_ID_LEN: usize = 8;
+const NONCE_LEN: usize = 12;
+const WRAPPED_KEY_LEN: usize = 32 + TAG_LEN;
+const PREFIX_LEN: usize = 7;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = 4 + KEY_ID_LEN + NONCE_LEN + WRAPPED_KEY_LEN + PREFIX_LEN + 4;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+fn crypto_error(message: &str) -> StorageError {
+    StorageError::Backend(format!("encryption: {}", message))
+}
+
+// A master key and the identifier written into the headers it protects
+#[derive(Clone)]
+pub struct MasterKey {
+    id: [u8; KEY_ID_LEN],
+    cipher: Aes256Gcm,
+}
+
+impl MasterKey {
+    pub fn from_hex(value: &str) -> Result<MasterKey, String> {
+        let bytes = hex::decode(value.trim()).map_err(|e| format!("invalid key: {}", e))?;
+        if bytes.len() != 32 {
+            return Err(format!("key must be 32 bytes, got {}", bytes.len()));
+        }
+        let mut id = [0u8; KEY_ID_LEN];
+        id.copy_from_slice(&Sha256::digest(&bytes)[..KEY_ID_LEN]);
+        let cipher = Aes256Gcm::new_from_slice(&bytes).map_err(|e| e.to_string())?;
+        Ok(MasterKey { id, cipher })
+    }
+
+    pub fn from_file(path: &str) -> Result<MasterKey, String> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| format!("reading {}: {}", path, e))?;
+        MasterKey::from_hex(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn id(&self) -> String {
+        hex::encode(self.id)
+    }
+}
+
+// The key new data is written with, plus older keys that can still be read
+#[derive(Clone)]
+pub struct Keyring {
+    current: MasterKey,
+    previous: Vec<MasterKey>,
+}
+
+impl Keyring {
+    // ENCRYPTION_KEY_FILE (or ENCRYPTION_KEY as hex) enables encryption; keys listed in
+    // ENCRYPTION_PREVIOUS_KEY_FILES remain readable until data is rotated off them
+    pub fn from_env() -> Result<Option<Keyring>, String> {
+        let current = match (env::var("ENCRYPTION_KEY_FILE"), env::var("ENCRYPTION_KEY")) {
+            (Ok(path), _) => MasterKey::from_file(&path)?,
+            (Err(_), Ok(value)) => MasterKey::from_hex(&value)?,
+            _ => return Ok(None),
+        };
+        let previous = env::var("ENCRYPTION_PREVIOUS_KEY_FILES")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(MasterKey::from_file)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(Keyring { current, previous }))
+    }
+
+    fn find(&self, id: &[u8]) -> Option<&MasterKey> {
+        std::iter::once(&self.current)
+            .chain(self.previous.iter())
+            .find(|key| key.id == id)
+    }
+}
+
+fn chunk_nonce(prefix: &[u8], counter: u32, last: bool) -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN..PREFIX_LEN + 4].copy_from_slice(&counter.to_be_bytes());
+    nonce[NONCE_LEN - 1] = last as u8;
+    nonce
+}
+
+// Per-object state: the unwrapped data key and the header that describes it
+struct Envelope {
+    header: Vec<u8>,
+    cipher: Aes256Gcm,
+    prefix: [u8; PREFIX_LEN],
+}
+
+impl Envelope {
+    fn create(master: &MasterKey) -> StorageResult<Envelope> {
+        let mut data_key = [0u8; 32];
+        OsRng.fill_bytes(&mut data_key);
+        let mut prefix = [0u8; PREFIX_LEN];
+        OsRng.fill_bytes(&mut prefix);
+
+        let cipher = Aes256Gcm::new_from_slice(&data_key).map_err(|_| crypto_error("bad key"))?;
+        Ok(Envelope {
+            header: build_header(master, &data_key, &prefix)?,
+            cipher,
+            prefix,
+        })
+    }
+
+    fn open(keyring: &Keyring, header: &[u8]) -> StorageResult<Envelope> {
+        if header.len() < HEADER_LEN || &header[..4] != MAGIC {
+            return Err(crypto_error("not an encrypted object"));
+        }
+        let key_id = &header[4..4 + KEY_ID_LEN];
+        let master = keyring
+            .find(key_id)
+            .ok_or_else(|| crypto_error(&format!("unknown key id {}", hex::encode(key_id))))?;
+
+        let data_key = unwrap_data_key(master, header)?;
+        let cipher = Aes256Gcm::new_from_slice(&data_key).map_err(|_| crypto_error("bad key"))?;
+        let offset = HEADER_LEN - PREFIX_LEN - 4;
+        let mut prefix = [0u8; PREFIX_LEN];
+        prefix.copy_from_slice(&header[offset..offset + PREFIX_LEN]);
+        let chunk_size = u32::from_be_bytes(header[HEADER_LEN - 4..HEADER_LEN].try_into().unwrap());
+        if chunk_size as usize != CHUNK_SIZE {
+            return Err(crypto_error("unsupported chunk size"));
+        }
+
+        Ok(Envelope {
+            header: header[..HEADER_LEN].to_vec(),
+            cipher,
+            prefix,
+        })
+    }
+
+    fn seal(&self, counter: u32, last: bool, chunk: &[u8]) -> StorageResult<Vec<u8>> {
+        let nonce = chunk_nonce(&self.prefix, counter, last);
+        self.cipher
+            .encrypt(Nonce::from_slice(&nonce), chunk)
+            .map_err(|_| crypto_error("failed to encrypt chunk"))
+    }
+
+    fn unseal(&self, counter: u32, last: bool, chunk: &[u8]) -> StorageResult<Vec<u8>> {
+        let nonce = chunk_nonce(&self.prefix, counter, last);
+        self.cipher
+            .decrypt(Nonce::from_slice(&nonce), chunk)
+            .map_err(|_| crypto_error("object failed authentication"))
+    }
+}
+
+fn build_header(
+    master: &MasterKey,
+    data_key: &[u8],
+    prefix: &[u8; PREFIX_LEN],
+) -> StorageResult<Vec<u8>> {
+    let mut wrap_nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut wrap_nonce);
+    let wrapped = master
+        .cipher
+        .encrypt(
+            Nonce::from_slice(&wrap_nonce),
+            Payload {
+                msg: data_key,
+                aad: &master.id,
+            },
+        )
+        .map_err(|_| crypto_error("failed to wrap data key"))?;
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&master.id);
+    header.extend_from_slice(&wrap_nonce);
+    header.extend_from_slice(&wrapped);
+    header.extend_from_slice(prefix);
+    header.extend_from_slice(&(CHUNK_SIZE as u32).to_be_bytes());
+    Ok(header)
+}
+
+fn unwrap_data_key(master: &MasterKey, header: &[u8]) -> StorageResult<Vec<u8>> {
+    let nonce_start = 4 + KEY_ID_LEN;
+    let wrapped_start = nonce_start + NONCE_LEN;
+    master
+        .cipher
+        .decrypt(
+            Nonce::from_slice(&header[nonce_start..wrapped_start]),
+            Payload {
+                msg: &header[wrapped_start..wrapped_start + WRAPPED_KEY_LEN],
+                aad: &master.id,
+            },
+        )
+        .map_err(|_| crypto_error("failed to unwrap data key"))
+}
+
+fn is_encrypted(data: &[u8]) -> bool {
+    data.len() >= HEADER_LEN && &data[..4] == MAGIC
+}
+
+fn encrypt(master: &MasterKey, plaintext: &[u8]) -> StorageResult<Vec<u8>> {
+    let envelope = Envelope::create(master)?;
+    let chunks = plaintext.len().div_ceil(CHUNK_SIZE).max(1);
+    let mut output = Vec::with_capacity(HEADER_LEN + plaintext.len() + chunks * TAG_LEN);
+    output.extend_from_slice(&envelope.header);
+    for index in 0..chunks {
+        let start = index * CHUNK_SIZE;
+        let end = (start + CHUNK_SIZE).min(plaintext.len());
+        let last = index + 1 == chunks;
+        output.extend(envelope.seal(index as u32, last, &plaintext[start..end])?);
+    }
+    Ok(output)
+}
+
+fn decrypt(keyring: &Keyring, data: &[u8]) -> StorageResult<Vec<u8>> {
+    let envelope = Envelope::open(keyring, data)?;
+    let body = &data[HEADER_LEN..];
+    let chunks = body.len().div_ceil(SEALED_CHUNK_SIZE).max(1);
+    let mut output = Vec::with_capacity(body.len());
+    for index in 0..chunks {
+        let start = index * SEALED_CHUNK_SIZE;
+        let end = (start + SEALED_CHUNK_SIZE).min(body.len());
+        let last = index + 1 == chunks;
+        output.extend(envelope.unseal(index as u32, last, &body[start..end])?);
+    }
+    Ok(output)
+}
+
+const SEALED_CHUNK_SIZE: usize = CHUNK_SIZE + TAG_LEN;
+
+// Number of sealed chunks in an encrypted object of `stored` bytes
+fn chunk_count(stored: u64) -> u64 {
+    let body = stored.saturating_sub(HEADER_LEN as u64);
+    body.div_ceil(SEALED_CHUNK_SIZE as u64).max(1)
+}
+
+// Plaintext length of an encrypted object of `stored` bytes
+fn plaintext_size(stored: u64) -> u64 {
+    let body = stored.saturating_sub(HEADER_LEN as u64);
+    body.saturating_sub(chunk_count(stored) * TAG_LEN as u64)
+}
+
+// Decrypts a run of sealed chunks as they arrive, keeping only the requested bytes
+struct RangeDecryptor {
+    envelope: Envelope,
+    sealed: ByteStream,
+    buffer: Vec<u8>,
+    counter: u64,
+    chunks: u64,
+    // Bytes to drop from the start of the first chunk
+    skip: usize,
+    remaining: u64,
+}
+
+fn decrypt_stream(decryptor: RangeDecryptor) -> ByteStream {
+    Box::pin(stream::try_unfold(decryptor, |mut state| async move {
+        if state.remaining == 0 {
+            return Ok(None);
+        }
+        while state.buffer.len() < SEALED_CHUNK_SIZE {
+            match state.sealed.next().await {
+                Some(data) => state.buffer.extend_from_slice(&data?),
+                None => break,
+            }
+        }
+        if state.buffer.is_empty() {
+            return Err(std::io::Error::other("encrypted object is truncated"));
+        }
+
+        let sealed: Vec<u8> = state
+            .buffer
+            .drain(..SEALED_CHUNK_SIZE.min(state.buffer.len()))
+            .collect();
+        let last = state.counter + 1 == state.chunks;
+        let plain = state
+            .envelope
+            .unseal(state.counter as u32, last, &sealed)
+            .map_err(std::io::Error::other)?;
+        let start = state.skip.min(plain.len());
+        let end = (start as u64 + state.remaining).min(plain.len() as u64) as usize;
+        state.skip = 0;
+        state.counter += 1;
+        state.remaining -= (end - start) as u64;
+        Ok(Some((Bytes::copy_from_slice(&plain[start..end]), state)))
+    }))
+}
+
+// Fill the buffer as far as possible, returning how many bytes were read
+async fn read_full(file: &mut fs::File, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        let read = file.read(&mut buffer[filled..]).await?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    Ok(filled)
+}
+
+// Encrypt a local file chunk by chunk into `target`
+async fn encrypt_file(master: &MasterKey, source: &Path, target: &Path) -> StorageResult<()> {
+    let envelope = Envelope::create(master)?;
+    let mut input = fs::File::open(source).await?;
+    let mut output = fs::File::create(target).await?;
+    output.write_all(&envelope.header).await?;
+
+    // Read one chunk ahead so the final chunk can be flagged
+    let mut current = vec![0u8; CHUNK_SIZE];
+    let mut next = vec![0u8; CHUNK_SIZE];
+    let mut current_len = read_full(&mut input, &mut current).await?;
+    let mut counter = 0u32;
+    loop {
+        let next_len = if current_len == CHUNK_SIZE {
+            read_full(&mut input, &mut next).await?
+        } else {
+            0
+        };
+        let last = next_len == 0;
+        output
+            .write_all(&envelope.seal(counter, last, &current[..current_len])?)
+            .await?;
+        if last {
+            break;
+        }
+        std::mem::swap(&mut current, &mut next);
+        current_len = next_len;
+        counter += 1;
+    }
+    output.flush().await?;
+    Ok(())
+}
+
+// Storage decorator that encrypts everything written and decrypts everything read.
+// Objects written before encryption was enabled are passed through unchanged.
+pub struct EncryptedStorage {
+    inner: Arc<dyn Storage>,
+    keyring: Keyring,
+}
+
+impl EncryptedStorage {
+    pub fn new(inner: Arc<dyn Storage>, keyring: Keyring) -> Self {
+        EncryptedStorage { inner, keyring }
+    }
+
+    // Stored size and header of an object; the header is empty for plaintext objects
+    async fn stored_header(&self, key: &str) -> StorageResult<(u64, Vec<u8>)> {
+        let stored = self.inner.size(key).await?;
+        let head = self
+            .inner
+            .get_range(key, 0, stored.min(HEADER_LEN as u64))
+            .await?;
+        let header = collect_stream(head).await?;
+        if is_encrypted(&header) {
+            Ok((stored, header))
+        } else {
+            Ok((stored, Vec::new()))
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for EncryptedStorage {
+    async fn put(&self, key: &str, data: Vec<u8>) -> StorageResult<()> {
+        let sealed = encrypt(&self.keyring.current, &data)?;
+        self.inner.put(key, sealed).await
+    }
+
+    async fn put_file(&self, key: &str, path: &Path) -> StorageResult<()> {
+        let sealed_path = path.with_extension("sealed");
+        let result = match encrypt_file(&self.keyring.current, path, &sealed_path).await {
+            Ok(()) => self.inner.put_file(key, &sealed_path).await,
+            Err(e) => Err(e),
+        };
+        let _ = fs::remove_file(&sealed_path).await;
+        result
+    }
+
+    async fn get(&self, key: &str) -> StorageResult<Vec<u8>> {
+        let data = self.inner.get(key).await?;
+        if is_encrypted(&data) {
+            decrypt(&self.keyring, &data)
+        } else {
+            Ok(data)
+        }
+    }
+
+    async fn delete(&self, key: &str) -> StorageResult<()> {
+        self.inner.delete(key).await
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> StorageResult<()> {
+        self.inner.rename(from, to).await
+    }
+
+    async fn list(&self, prefix: &str) -> StorageResult<Vec<String>> {
+        self.inner.list(prefix).await
+    }
+
+    async fn modified(&self, key: &str) -> StorageResult<Option<SystemTime>> {
+        self.inner.modified(key).await
+    }
+
+    async fn size(&self, key: &str) -> StorageResult<u64> {
+        let (stored, header) = self.stored_header(key).await?;
+        if header.is_empty() {
+            Ok(stored)
+        } else {
+            Ok(plaintext_size(stored))
+        }
+    }
+
+    // Only the sealed chunks that overlap the range are fetched and decrypted
+    async fn get_range(&self, key: &str, offset: u64, length: u64) -> StorageResult<ByteStream> {
+        let (stored, header) = self.stored_header(key).await?;
+        if header.is_empty() {
+            return self.inner.get_range(key, offset, length).await;
+        }
+        if length == 0 {
+            return Ok(empty_stream());
+        }
+        let envelope = Envelope::open(&self.keyring, &header)?;
+
+        let chunks = chunk_count(stored);
+        let first = offset / CHUNK_SIZE as u64;
+        let last = ((offset + length - 1) / CHUNK_SIZE as u64).min(chunks - 1);
+        let start = HEADER_LEN as u64 + first * SEALED_CHUNK_SIZE as u64;
+        let end = (HEADER_LEN as u64 + (last + 1) * SEALED_CHUNK_SIZE as u64).min(stored);
+        let sealed = self
+            .inner
+            .get_range(key, start, end.saturating_sub(start))
+            .await?;
+        Ok(decrypt_stream(RangeDecryptor {
+            envelope,
+            sealed,
+            buffer: Vec::with_capacity(SEALED_CHUNK_SIZE),
+            counter: first,
+            chunks,
+            skip: (offset - first * CHUNK_SIZE as u64) as usize,
+            remaining: length,
+        }))
+    }
+}
+
+// Wrap the storage backend with encryption when a key is configured
+pub fn wrap_from_env(storage: Arc<dyn Storage>) -> Result<Arc<dyn Storage>, String> {
+    match Keyring::from_env()? {
+        Some(keyring) => {
+            println!(
+                "Encryption at rest enabled with key {}",
+                keyring.current.id()
+            );
+            Ok(Arc::new(EncryptedStorage::new(storage, keyring)))
+        }
+        None => Ok(storage),
+    }
+}
+
+// Move every stored object onto the current key. Objects under an old key only need
+// their data key re-wrapped, so the sealed chunks are copied as they are behind a new
+// header; plaintext objects from before encryption are encrypted. Objects pass through
+// local files so long recordings are never held in memory.
+pub async fn rotate_keys(
+    storage: &dyn Storage,
+    keyring: &Keyring,
+) -> Result<(usize, usize), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let scratch = tempfile::tempdir()?;
+    let plain_path = scratch.path().join("object");
+    let sealed_path = scratch.path().join("object.sealed");
+    let mut rotated = 0;
+    let mut unchanged = 0;
+    for dir in ARTIFACT_DIRS {
+        for key in storage.list(&format!("{}/", dir)).await? {
+            let stored = storage.size(&key).await?;
+            let head = storage
+                .get_range(&key, 0, stored.min(HEADER_LEN as u64))
+                .await?;
+            let header = collect_stream(head).await?;
+            if is_encrypted(&header) {
+                if header[4..4 + KEY_ID_LEN] == keyring.current.id {
+                    unchanged += 1;
+                    continue;
+                }
+                let envelope = Envelope::open(keyring, &header)?;
+                let old_key = keyring.find(&header[4..4 + KEY_ID_LEN]).unwrap();
+                let data_key = unwrap_data_key(old_key, &header)?;
+                let rewrapped = build_header(&keyring.current, &data_key, &envelope.prefix)?;
+                let mut output = fs::File::create(&sealed_path).await?;
+                output.write_all(&rewrapped).await?;
+                let mut body = storage
+                    .get_range(&key, HEADER_LEN as u64, stored - HEADER_LEN as u64)
+                    .await?;
+                while let Some(chunk) = body.next().await {
+                    output.write_all(&chunk?).await?;
+                }
+                output.flush().await?;
+            } else {
+                copy_to_file(storage, &key, &plain_path).await?;
+                encrypt_file(&keyring.current, &plain_path, &sealed_path).await?;
+                fs::remove_file(&plain_path).await?;
+            }
+            storage.put_file(&key, &sealed_path).await?;
+            println!("Rotated {}", key);
+            rotated += 1;
+        }
+    }
+    Ok((rotated, unchanged))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStorage;
+
+    fn key(byte: u8) -> MasterKey {
+        MasterKey::from_hex(&hex::encode([byte; 32])).unwrap()
+    }
+
+    fn keyring() -> Keyring {
+        Keyring {
+            current: key(1),
+            previous: Vec::new(),
+        }
+    }
+
+    fn sample(length: usize) -> Vec<u8> {
+        (0..length).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn sealed_chunk(sealed: &[u8], index: usize) -> &[u8] {
+        let start = HEADER_LEN + index * SEALED_CHUNK_SIZE;
+        &sealed[start..(start + SEALED_CHUNK_SIZE).min(sealed.len())]
+    }
+
+    #[test]
+    fn round_trips_at_chunk_boundaries() {
+        let keyring = keyring();
+        for length in [
+            0,
+            1,
+            CHUNK_SIZE - 1,
+            CHUNK_SIZE,
+            CHUNK_SIZE + 1,
+            3 * CHUNK_SIZE + 5,
+        ] {
+            let data = sample(length);
+            let sealed = encrypt(&keyring.current, &data).unwrap();
+            assert!(is_encrypted(&sealed));
+            assert_eq!(plaintext_size(sealed.len() as u64), length as u64);
+            assert_eq!(
+                decrypt(&keyring, &sealed).unwrap(),
+                data,
+                "length {}",
+                length
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_dropped_final_chunk() {
+        let keyring = keyring();
+        let sealed = encrypt(&keyring.current, &sample(3 * CHUNK_SIZE)).unwrap();
+        // The remaining chunks are intact, but the new final one was not sealed as last
+        let truncated = &sealed[..HEADER_LEN + 2 * SEALED_CHUNK_SIZE];
+        assert!(decrypt(&keyring, truncated).is_err());
+    }
+
+    #[test]
+    fn rejects_appended_chunk() {
+        let keyring = keyring();
+        let sealed = encrypt(&keyring.current, &sample(2 * CHUNK_SIZE)).unwrap();
+        let mut extended = sealed.clone();
+        extended.extend_from_slice(sealed_chunk(&sealed, 1));
+        assert!(decrypt(&keyring, &extended).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_and_reordered_chunks() {
+        let keyring = keyring();
+        let sealed = encrypt(&keyring.current, &sample(2 * CHUNK_SIZE + 100)).unwrap();
+        assert!(decrypt(&keyring, &sealed[..sealed.len() - 1]).is_err());
+
+        let mut reordered = sealed[..HEADER_LEN].to_vec();
+        reordered.extend_from_slice(sealed_chunk(&sealed, 1));
+        reordered.extend_from_slice(sealed_chunk(&sealed, 0));
+        reordered.extend_from_slice(sealed_chunk(&sealed, 2));
+        assert!(decrypt(&keyring, &reordered).is_err());
+
+        let mut flipped = sealed.clone();
+        flipped[HEADER_LEN + 10] ^= 1;
+        assert!(decrypt(&keyring, &flipped).is_err());
+    }
+
+    #[test]
+    fn previous_keys_still_decrypt() {
+        let sealed = encrypt(&key(2), b"sealed under an old key").unwrap();
+        assert!(decrypt(&keyring(), &sealed).is_err());
+        let rotated = Keyring {
+            current: key(1),
+            previous: vec![key(2)],
+        };
+        assert_eq!(
+            decrypt(&rotated, &sealed).unwrap(),
+            b"sealed under an old key"
+        );
+    }
+
+    #[tokio::test]
+    async fn decrypts_ranges_across_chunk_boundaries() {
+        let root = tempfile::tempdir().unwrap();
+        let inner: Arc<dyn Storage> = Arc::new(LocalStorage::new(root.path()));
+        let storage = EncryptedStorage::new(inner.clone(), keyring());
+        let data = sample(3 * CHUNK_SIZE + CHUNK_SIZE / 2);
+        storage.put("uploads/a.mp3", data.clone()).await.unwrap();
+        assert_eq!(
+            storage.size("uploads/a.mp3").await.unwrap(),
+            data.len() as u64
+        );
+
+        let chunk = CHUNK_SIZE as u64;
+        for (offset, length) in [
+            (0, 10),
+            (chunk - 10, 20),
+            (chunk, chunk),
+            (chunk / 2, 2 * chunk),
+            (data.len() as u64 - 7, 7),
+            (0, data.len() as u64),
+        ] {
+            let stream = storage
+                .get_range("uploads/a.mp3", offset, length)
+                .await
+                .unwrap();
+            let range = collect_stream(stream).await.unwrap();
+            let (start, end) = (offset as usize, (offset + length) as usize);
+            assert_eq!(range, &data[start..end], "range {}+{}", offset, length);
+        }
+
+        // Objects stored before encryption was enabled are read as they are
+        inner
+            .put("uploads/plain.txt", b"plain".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(storage.get("uploads/plain.txt").await.unwrap(), b"plain");
+        let stream = storage.get_range("uploads/plain.txt", 1, 3).await.unwrap();
+        assert_eq!(collect_stream(stream).await.unwrap(), b"lai");
+    }
+}
@@ -1,402 +1,3155 @@
-use actix_cors::Cors;
-use actix_multipart::Multipart;
-use actix_web::{get, http, post, web, App, HttpResponse, HttpServer, Responder};
-use futures_util::stream::StreamExt as _;
-use reqwest::Client;
-use serde::Deserialize;
-use serde_json::json;
-use std::env;
-use std::fs::File;
-use std::io::Write;
-use std::path::Path;
-use tokio::fs;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use uuid::Uuid;
-
-mod audio_processing;
-
-#[derive(Deserialize)]
-struct TranscriptionRequest {
-    transcription: String, // This will be the UUID filename
-}
-
-// Helper function to read transcription content from the file asynchronously
-async fn read_transcription_content(uuid_filename: &str) -> Result<String, std::io::Error> {
-    let file_path = if uuid_filename.ends_with(".txt") {
-        format!("./transcriptions/{}", uuid_filename)
-    } else {
-        format!("./transcriptions/{}.txt", uuid_filename)
-    };
-
-    let mut file = fs::File::open(file_path).await?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).await?;
-    Ok(contents)
-}
-
-// Helper function to call OpenAI API with the extracted transcription text
-async fn call_openai_api(
-    transcription_text: String,
-    system_message: &str,
-) -> Result<String, reqwest::Error> {
-    let client = Client::new();
-    let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY must be set");
-
-    let request_body = serde_json::json!({
-        "model": "gpt-4o-mini",
-        "temperature": 0.0,
-        "messages": [
-            {
-                "role": "system",
-                "content": system_message
-            },
-            {
-                "role": "user",
-                "content": transcription_text
-            }
-        ]
-    });
-
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .bearer_auth(api_key)
-        .json(&request_body)
-        .send()
-        .await;
-
-    match response {
-        Ok(successful_response) => {
-            let json_response = successful_response.json::<serde_json::Value>().await?;
-            let result = json_response["choices"][0]["message"]["content"]
-                .as_str()
-                .unwrap_or("No response")
-                .to_string();
-            Ok(result)
-        }
-        Err(e) => Err(e),
-    }
-}
-
-// Save result to a file using the same UUID name asynchronously
-async fn save_to_file(
-    directory: &str,
-    uuid_filename: &str,
-    content: &str,
-) -> Result<(), std::io::Error> {
-    let file_path = if uuid_filename.ends_with(".txt") {
-        format!("{}/{}", directory, uuid_filename)
-    } else {
-        format!("{}/{}.txt", directory, uuid_filename)
-    };
-
-    let path = std::path::Path::new(&file_path);
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).await?;
-    }
-    let mut file = fs::File::create(file_path).await?;
-    file.write_all(content.as_bytes()).await?;
-    Ok(())
-}
-
-// Endpoint for generating summary from transcription and returning it
-#[post("/summarize")]
-async fn summarize(transcription: web::Json<TranscriptionRequest>) -> impl Responder {
-    let uuid_filename = &transcription.transcription;
-
-    match read_transcription_content(uuid_filename).await {
-        Ok(transcription_text) => {
-            let system_message = "Summarize the following transcription...";
-            match call_openai_api(transcription_text, system_message).await {
-                Ok(summary) => {
-                    // Save the generated summary to a file
-                    if let Err(e) = save_to_file("./summaries", uuid_filename, &summary).await {
-                        return HttpResponse::InternalServerError()
-                            .json(json!({"error": format!("Error saving summary: {}", e)}));
-                    }
-                    // Return the summary in the response
-                    HttpResponse::Ok().json(json!({
-                        "content": summary
-                    }))
-                }
-                Err(_) => HttpResponse::InternalServerError()
-                    .json(json!({"error": "Error generating summary"})),
-            }
-        }
-        Err(_) => HttpResponse::InternalServerError()
-            .json(json!({"error": "Error reading transcription"})),
-    }
-}
-
-// Repeat similar changes for key points, action items, and participants
-
-#[post("/key_points")]
-async fn key_points(transcription: web::Json<TranscriptionRequest>) -> impl Responder {
-    let uuid_filename = &transcription.transcription;
-
-    match read_transcription_content(uuid_filename).await {
-        Ok(transcription_text) => {
-            let system_message = "Extract key points from the transcription...";
-            match call_openai_api(transcription_text, system_message).await {
-                Ok(key_points) => {
-                    // Save the generated key points to a file
-                    if let Err(e) = save_to_file("./key_points", uuid_filename, &key_points).await {
-                        return HttpResponse::InternalServerError()
-                            .json(json!({"error": format!("Error saving key points: {}", e)}));
-                    }
-                    // Return the key points in the response
-                    HttpResponse::Ok().json(json!({
-                        "content": key_points
-                    }))
-                }
-                Err(_) => HttpResponse::InternalServerError()
-                    .json(json!({"error": "Error extracting key points"})),
-            }
-        }
-        Err(_) => HttpResponse::InternalServerError()
-            .json(json!({"error": "Error reading transcription"})),
-    }
-}
-
-// Endpoint for extracting action items from transcription
-#[post("/action_items")]
-async fn action_items(transcription: web::Json<TranscriptionRequest>) -> impl Responder {
-    let uuid_filename = &transcription.transcription;
-
-    match read_transcription_content(uuid_filename).await {
-        Ok(transcription_text) => {
-            let system_message = "Extract action items from the transcription...";
-            match call_openai_api(transcription_text, system_message).await {
-                Ok(action_items) => {
-                    // Save the generated action items to a file
-                    if let Err(e) =
-                        save_to_file("./action_items", uuid_filename, &action_items).await
-                    {
-                        return HttpResponse::InternalServerError()
-                            .json(json!({"error": format!("Error saving action items: {}", e)}));
-                    }
-                    // Return the action items in the response
-                    HttpResponse::Ok().json(json!({
-                        "content": action_items
-                    }))
-                }
-                Err(_) => HttpResponse::InternalServerError()
-                    .json(json!({"error": "Error extracting action items"})),
-            }
-        }
-        Err(_) => HttpResponse::InternalServerError()
-            .json(json!({"error": "Error reading transcription"})),
-    }
-}
-
-// Endpoint for extracting participants from transcription
-#[post("/participants")]
-async fn participants(transcription: web::Json<TranscriptionRequest>) -> impl Responder {
-    let uuid_filename = &transcription.transcription;
-
-    match read_transcription_content(uuid_filename).await {
-        Ok(transcription_text) => {
-            let system_message = "Extract participants and their details from the transcription...";
-            match call_openai_api(transcription_text, system_message).await {
-                Ok(participants) => {
-                    // Save the generated participants to a file
-                    if let Err(e) =
-                        save_to_file("./participants", uuid_filename, &participants).await
-                    {
-                        return HttpResponse::InternalServerError()
-                            .json(json!({"error": format!("Error saving participants: {}", e)}));
-                    }
-                    // Return the participants in the response
-                    HttpResponse::Ok().json(json!({
-                        "content": participants
-                    }))
-                }
-                Err(_) => HttpResponse::InternalServerError()
-                    .json(json!({"error": "Error extracting participants"})),
-            }
-        }
-        Err(_) => HttpResponse::InternalServerError()
-            .json(json!({"error": "Error reading transcription"})),
-    }
-}
-#[post("/upload")]
-async fn upload_audio(mut payload: Multipart) -> impl Responder {
-    // Create a unique filename for the uploaded file
-    let uuid = Uuid::new_v4();
-    let file_path = format!("./uploads/{}.mp3", uuid);
-
-    // Clone file_path for use inside web::block to avoid lifetime issues
-    let file_path_clone = file_path.clone();
-
-    // Save the uploaded file
-    let mut file = web::block(move || File::create(&file_path_clone))
-        .await
-        .expect("Failed to create file for saving the uploaded audio")
-        .expect("Failed to open the file");
-
-    // Process each field in the multipart payload
-    while let Some(item) = payload.next().await {
-        let mut field = item.expect("Failed to process multipart field");
-
-        // Process the field stream
-        while let Some(chunk) = field.next().await {
-            let data = chunk.expect("Failed to read chunk");
-
-            // Write the chunk to the file
-            file = web::block(move || {
-                file.write_all(&data)?;
-                Ok::<_, std::io::Error>(file)
-            })
-            .await
-            .expect("Failed to write chunk to file")
-            .expect("File writing failed");
-        }
-    }
-
-    // Call the transcription process using the UUID filename
-    match process_audio_file(file_path.clone()).await {
-        Ok(transcription_filename) => {
-            // Return a JSON response instead of plain text
-            HttpResponse::Ok().json(serde_json::json!({
-                "uploaded_file": file_path,
-                "transcription_file": transcription_filename
-            }))
-        }
-        Err(e) => HttpResponse::InternalServerError()
-            .json(serde_json::json!({ "error": format!("Error: {}", e) })),
-    }
-}
-
-// Download a file from the server
-#[get("/download/{category}/{file_name}")]
-async fn download_file(path: web::Path<(String, String)>) -> impl Responder {
-    let (category, file_name) = path.into_inner();
-    let file_path = format!("./{}/{}", category, file_name);
-
-    if let Ok(content) = fs::read(&file_path).await {
-        HttpResponse::Ok()
-            .content_type("text/plain")
-            .insert_header((
-                "Content-Disposition",
-                format!("attachment; filename={}", file_name),
-            ))
-            .body(content)
-    } else {
-        HttpResponse::NotFound().body("File not found")
-    }
-}
-
-#[get("/health")]
-async fn health() -> impl Responder {
-    println!("Health check requested");
-    HttpResponse::Ok().body("Server is running")
-}
-
-async fn process_audio_file(
-    file_path: String,
-) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
-    println!("Starting transcription process for file: {}", file_path);
-
-    // Load environment variables
-
-    // Get the OpenAI API key from the environment
-    let openai_api_key = std::env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY must be set");
-
-    // Debug message for starting transcription process
-    println!("API key loaded. Starting the transcription process...");
-
-    // Process and transcribe the audio file using the existing logic
-    let transcriptions = audio_processing::split_audio_by_size_and_transcribe(
-        &file_path,
-        1024 * 1024 * 10, // Example max segment size (5MB)
-        &openai_api_key,
-    )
-    .await?;
-
-    // Debug message for checking if transcriptions were received
-    println!("Transcriptions received: {:?}", transcriptions);
-
-    // Combine all the transcriptions into a single line (remove all line breaks)
-    let transcription_combined = transcriptions.join(" ");
-    println!("Combined transcription: {}", transcription_combined);
-
-    // Ensure the directory exists
-    if let Err(e) = std::fs::create_dir_all("./transcriptions") {
-        println!("Failed to create directory: {:?}", e);
-        return Err(Box::new(e));
-    }
-
-    // Generate a unique file name
-    let transcription_filename = format!("./transcriptions/{}.txt", Uuid::new_v4());
-
-    // Attempt to create the file
-    let mut file = match File::create(&transcription_filename) {
-        Ok(f) => f,
-        Err(e) => {
-            println!("Failed to create file: {:?}", e);
-            return Err(Box::new(e));
-        }
-    };
-
-    // Attempt to write the combined transcription to the file
-    if let Err(e) = file.write_all(transcription_combined.as_bytes()) {
-        println!("Failed to write to file: {:?}", e);
-        return Err(Box::new(e));
-    }
-
-    // Debug message to confirm the transcription has been saved
-    println!(
-        "Transcription successfully written to file: {}",
-        transcription_filename
-    );
-
-    // Return only the file name, not the full path
-    let file_name = Path::new(&transcription_filename)
-        .file_name()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_string();
-
-    Ok(file_name)
-}
-
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
-
-    // Ensure the necessary directories exist
-    fs::create_dir_all("./uploads").await?;
-    fs::create_dir_all("./transcriptions").await?;
-    fs::create_dir_all("./summaries").await?;
-    fs::create_dir_all("./key_points").await?;
-    fs::create_dir_all("./action_items").await?;
-    fs::create_dir_all("./participants").await?;
-
-    // Start the Actix Web server
-    HttpServer::new(|| {
-        App::new()
-            .wrap(
-                // Configure CORS properly
-                Cors::default()
-                    .allow_any_origin()
-                    .allowed_methods(vec!["GET", "POST", "OPTIONS"])
-                    .allowed_headers(vec![
-                        http::header::AUTHORIZATION,
-                        http::header::ACCEPT,
-                        http::header::CONTENT_TYPE,
-                    ])
-                    .supports_credentials()
-                    .max_age(3600),
-            )
-            .service(upload_audio)
-            .service(download_file)
-            .service(health)
-            .service(summarize)
-            .service(key_points)
-            .service(action_items)
-            .service(participants)
-    })
-    .bind(("0.0.0.0", port.parse().unwrap()))?
-    .run()
-    .await
-}
+use actix_cors::Cors;
+use actix_multipart::Multipart;
+use actix_web::{
+    delete, get, http, patch, post, put, route, web, App, HttpRequest, HttpResponse, HttpServer,
+    Responder,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::future::join_all;
+use futures_util::stream::StreamExt as _;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::fs;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+mod analyses;
+mod analysis;
+mod audio_processing;
+mod corrections;
+mod court;
+mod db;
+mod deletion;
+mod diarization;
+mod download;
+mod encryption;
+mod pdf;
+mod prompts;
+mod qa;
+mod recordings;
+mod report;
+mod revisions;
+mod speakers;
+mod storage;
+mod structured;
+mod transcript;
+mod usage;
+mod vocabulary;
+mod waveform;
+
+use analyses::{AnalysisType, BuiltinAnalysis};
+use analysis::{AnalysisError, OutputEvent, OutputSink};
+use prompts::{PromptLibrary, PromptVariables};
+use storage::{Storage, StorageError};
+use structured::{Participants, SpeakerAssignments, StructuredOutput};
+use transcript::TranscriptSegment;
+use usage::{PriceTable, UsageMeter};
+
+// Local scratch space for uploads while ffmpeg processes them
+const WORK_DIR: &str = "./work";
+
+#[derive(Deserialize)]
+struct TranscriptionRequest {
+    transcription: String, // This will be the UUID filename
+    language: Option<String>,
+    stream: Option<bool>,
+    force: Option<bool>,
+}
+
+// Helper function to read transcription content from storage asynchronously
+async fn read_transcription_content(
+    storage: &dyn Storage,
+    uuid_filename: &str,
+) -> Result<String, StorageError> {
+    let key = if uuid_filename.ends_with(".txt") {
+        format!("transcriptions/{}", uuid_filename)
+    } else {
+        format!("transcriptions/{}.txt", uuid_filename)
+    };
+
+    let contents = storage.get(&key).await?;
+    Ok(String::from_utf8_lossy(&contents).into_owned())
+}
+
+// Timestamped segments for the transcript, when they were stored alongside it, with the
+// speaker names reviewers have assigned
+async fn read_transcript_segments(
+    db: &db::Db,
+    storage: &dyn Storage,
+    uuid_filename: &str,
+) -> Option<Vec<TranscriptSegment>> {
+    let id = recordings::recording_id_from_filename(uuid_filename);
+    let mut segments = transcript::load_segments(storage, id)
+        .await
+        .ok()
+        .flatten()?;
+    apply_speaker_names(db, id, &mut segments);
+    Some(segments)
+}
+
+fn apply_speaker_names(db: &db::Db, recording_id: &str, segments: &mut [TranscriptSegment]) {
+    if !transcript::has_speakers(segments) || !recordings::is_valid_id(recording_id) {
+        return;
+    }
+    match db.speakers(recording_id) {
+        Ok(names) => speakers::apply_names(segments, &names),
+        Err(e) => println!("Could not read speaker names for {}: {:?}", recording_id, e),
+    }
+}
+
+// Save result to storage using the same UUID name asynchronously
+async fn save_to_file(
+    storage: &dyn Storage,
+    directory: &str,
+    uuid_filename: &str,
+    content: &str,
+) -> Result<(), StorageError> {
+    let key = if uuid_filename.ends_with(".txt") {
+        format!("{}/{}", directory, uuid_filename)
+    } else {
+        format!("{}/{}.txt", directory, uuid_filename)
+    };
+
+    storage.put(&key, content.as_bytes().to_vec()).await
+}
+
+// Save structured output as JSON next to the text version of the same analysis
+async fn save_json<T: serde::Serialize>(
+    storage: &dyn Storage,
+    directory: &str,
+    uuid_filename: &str,
+    data: &T,
+) -> Result<(), StorageError> {
+    let id = recordings::recording_id_from_filename(uuid_filename);
+    let contents =
+        serde_json::to_vec_pretty(data).map_err(|e| StorageError::Backend(e.to_string()))?;
+    storage
+        .put(&format!("{}/{}.json", directory, id), contents)
+        .await
+}
+
+// Values for the prompt template variables, taken from the recording metadata and any
+// participants already extracted for it
+async fn prompt_variables(
+    db: &db::Db,
+    storage: &dyn Storage,
+    uuid_filename: &str,
+    language: Option<&str>,
+) -> PromptVariables {
+    let id = recordings::recording_id_from_filename(uuid_filename);
+    let recording = if recordings::is_valid_id(id) {
+        db.get_recording(id).ok().flatten()
+    } else {
+        None
+    };
+
+    let names = match storage.get(&format!("participants/{}.json", id)).await {
+        Ok(contents) => serde_json::from_slice::<Participants>(&contents)
+            .map(|p| {
+                p.participants
+                    .into_iter()
+                    .map(|p| p.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_default(),
+        Err(_) => String::new(),
+    };
+
+    PromptVariables {
+        title: recording
+            .as_ref()
+            .and_then(|r| r.title.clone())
+            .unwrap_or_else(|| "Untitled hearing".to_string()),
+        date: recording
+            .as_ref()
+            .map(|r| r.created_at.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "an unknown date".to_string()),
+        participants: if names.is_empty() {
+            "not yet identified".to_string()
+        } else {
+            names
+        },
+        language: language
+            .map(|l| l.to_string())
+            .or_else(|| env::var("OUTPUT_LANGUAGE").ok())
+            .unwrap_or_else(|| "English".to_string()),
+    }
+}
+
+// Attach a saved analysis to its recording, if the transcript belongs to one
+fn record_analysis(
+    db: &db::Db,
+    input: &AnalysisInput,
+    kind: &str,
+    directory: &str,
+    extension: &str,
+    prompt_version: &str,
+    cache_key: &str,
+) {
+    let id = recordings::recording_id_from_filename(&input.uuid_filename);
+    if !recordings::is_valid_id(id) {
+        return;
+    }
+
+    let file = format!("{}/{}.{}", directory, id, extension);
+    let mut artifact = recordings::Artifact::new(kind, &file, Some(analysis::CHAT_MODEL));
+    artifact.prompt_version = Some(prompt_version.to_string());
+    artifact.cache_key = Some(cache_key.to_string());
+    artifact.transcript_revision = Some(input.transcript_revision);
+    if let Err(e) = db.put_artifact(id, &artifact) {
+        println!("Could not update recording metadata for {}: {:?}", id, e);
+    }
+}
+
+// Record what the upstream calls made for a request consumed
+fn record_usage(db: &db::Db, uuid_filename: &str, operation: &str, meter: &UsageMeter) {
+    let entries = meter.take();
+    if entries.is_empty() {
+        return;
+    }
+    let id = recordings::recording_id_from_filename(uuid_filename);
+    let recording_id = recordings::is_valid_id(id).then_some(id);
+    if let Err(e) = db.record_usage(recording_id, operation, &entries) {
+        println!("Could not record usage for {}: {:?}", uuid_filename, e);
+    }
+}
+
+// A finished analysis as stored. Built-in analyses are addressed by their type name, custom
+// ones by the ID they were saved under.
+#[derive(Serialize)]
+struct SavedAnalysis {
+    analysis_id: String,
+    kind: String,
+    file: String,
+    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<serde_json::Value>,
+    prompt_version: String,
+    cache: CacheInfo,
+}
+
+// Whether a result was reused from an earlier request with identical inputs
+#[derive(Serialize)]
+struct CacheInfo {
+    hit: bool,
+    key: String,
+    generated_at: DateTime<Utc>,
+}
+
+enum AnalysisFailure {
+    Transcript(StorageError),
+    Template(String),
+    Model(AnalysisError),
+    Save(StorageError),
+    Cancelled,
+}
+
+impl AnalysisFailure {
+    fn message(&self, analysis_type: &AnalysisType) -> String {
+        match (self, analysis_type) {
+            (AnalysisFailure::Transcript(_), _) => "Error reading transcription".to_string(),
+            (AnalysisFailure::Template(name), _) => format!("Missing prompt template: {}", name),
+            (AnalysisFailure::Model(e), AnalysisType::Builtin(builtin)) => {
+                format!("{}: {}", builtin.failure_message(), e)
+            }
+            (AnalysisFailure::Model(e), custom) => {
+                format!("Error generating {}: {}", custom.label(), e)
+            }
+            (AnalysisFailure::Save(e), analysis_type) => {
+                format!("Error saving {}: {}", analysis_type.label(), e)
+            }
+            (AnalysisFailure::Cancelled, _) => "Stopped after the client disconnected".to_string(),
+        }
+    }
+
+    // Failures of the model API are reported as a bad gateway rather than our own error
+    fn status(&self) -> http::StatusCode {
+        match self {
+            AnalysisFailure::Model(_) => http::StatusCode::BAD_GATEWAY,
+            _ => http::StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+// Everything an analysis reads, loaded once so several analyses can share it
+struct AnalysisInput {
+    uuid_filename: String,
+    transcript: String,
+    transcript_hash: String,
+    // Revision the transcript was at when it was read
+    transcript_revision: u32,
+    segments: Option<Vec<TranscriptSegment>>,
+    variables: PromptVariables,
+}
+
+async fn load_analysis_input(
+    db: &db::Db,
+    storage: &dyn Storage,
+    uuid_filename: &str,
+    language: Option<&str>,
+) -> Result<AnalysisInput, StorageError> {
+    // Read before the transcript, so an edit in between marks the analysis stale rather
+    // than hiding that it is out of date
+    let id = recordings::recording_id_from_filename(uuid_filename);
+    let transcript_revision = if recordings::is_valid_id(id) {
+        db.transcript_revision(id).unwrap_or(1)
+    } else {
+        1
+    };
+    let transcript = read_transcription_content(storage, uuid_filename).await?;
+    let segments = read_transcript_segments(db, storage, uuid_filename).await;
+
+    // Speaker labels change what the model sees, so they are part of the transcript hash
+    let mut hasher = Sha256::new();
+    hasher.update(transcript.as_bytes());
+    if let Some(segments) = segments.as_deref().filter(|s| transcript::has_speakers(s)) {
+        hasher.update(transcript::turn_text(&transcript::speaker_turns(segments)).as_bytes());
+    }
+    Ok(AnalysisInput {
+        uuid_filename: uuid_filename.to_string(),
+        transcript_hash: hex::encode(hasher.finalize()),
+        transcript_revision,
+        transcript,
+        segments,
+        variables: prompt_variables(db, storage, uuid_filename, language).await,
+    })
+}
+
+// The flow shared by every analysis: read the transcript, render the prompt template, run
+// the model and save the result where the analysis type keeps it
+async fn run_saved_analysis(
+    db: &db::Db,
+    storage: &dyn Storage,
+    prompts: &PromptLibrary,
+    uuid_filename: &str,
+    analysis_type: &AnalysisType,
+    language: Option<&str>,
+    force: bool,
+) -> Result<SavedAnalysis, AnalysisFailure> {
+    let input = load_analysis_input(db, storage, uuid_filename, language)
+        .await
+        .map_err(AnalysisFailure::Transcript)?;
+    run_loaded_analysis(db, storage, prompts, &input, analysis_type, None, force).await
+}
+
+// Results are reused when the transcript, analysis, prompt template version and model all
+// match. The rendered prompt is part of the key too, since template variables such as the
+// output language change it without changing the template version.
+fn analysis_cache_key(
+    input: &AnalysisInput,
+    kind: &str,
+    prompt_version: &str,
+    system_message: &str,
+) -> String {
+    let mut hasher = Sha256::new();
+    for part in [
+        input.transcript_hash.as_str(),
+        kind,
+        prompt_version,
+        analysis::CHAT_MODEL,
+        system_message,
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hex::encode(hasher.finalize())
+}
+
+// A stored result generated from the same inputs, if it is still available
+async fn cached_analysis(
+    db: &db::Db,
+    storage: &dyn Storage,
+    uuid_filename: &str,
+    analysis_type: &AnalysisType,
+    cache_key: &str,
+) -> Option<SavedAnalysis> {
+    let id = recordings::recording_id_from_filename(uuid_filename);
+    if !recordings::is_valid_id(id) {
+        return None;
+    }
+    let kind = analysis_type.kind();
+    let artifact = db
+        .find_cached_artifact(id, &kind, cache_key)
+        .ok()
+        .flatten()?;
+    let content = storage.get(&artifact.file).await.ok()?;
+
+    let data = match analysis_type {
+        AnalysisType::Builtin(builtin) if builtin.format().is_some() => {
+            let json_kind = format!("{}_json", builtin.directory());
+            let json_artifact = db
+                .find_cached_artifact(id, &json_kind, cache_key)
+                .ok()
+                .flatten()?;
+            let data = storage.get(&json_artifact.file).await.ok()?;
+            Some(serde_json::from_slice(&data).ok()?)
+        }
+        _ => None,
+    };
+    let analysis_id = match analysis_type {
+        AnalysisType::Builtin(builtin) => builtin.name().to_string(),
+        AnalysisType::Custom(_) => artifact
+            .file
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches(".txt")
+            .to_string(),
+    };
+
+    Some(SavedAnalysis {
+        analysis_id,
+        kind,
+        file: artifact.file,
+        content: String::from_utf8_lossy(&content).into_owned(),
+        data,
+        prompt_version: artifact.prompt_version.unwrap_or_default(),
+        cache: CacheInfo {
+            hit: true,
+            key: cache_key.to_string(),
+            generated_at: artifact.created_at,
+        },
+    })
+}
+
+async fn run_loaded_analysis(
+    db: &db::Db,
+    storage: &dyn Storage,
+    prompts: &PromptLibrary,
+    input: &AnalysisInput,
+    analysis_type: &AnalysisType,
+    sink: Option<OutputSink<'_>>,
+    force: bool,
+) -> Result<SavedAnalysis, AnalysisFailure> {
+    let uuid_filename = input.uuid_filename.as_str();
+    let template = match analysis_type {
+        AnalysisType::Builtin(builtin) => prompts
+            .get(builtin.name())
+            .ok_or_else(|| AnalysisFailure::Template(builtin.name().to_string()))?,
+        AnalysisType::Custom(template) => template.clone(),
+    };
+    let system_message = template.render(&input.variables);
+    let cache_key = analysis_cache_key(
+        input,
+        &analysis_type.kind(),
+        &template.version,
+        &system_message,
+    );
+    if !force {
+        if let Some(saved) =
+            cached_analysis(db, storage, uuid_filename, analysis_type, &cache_key).await
+        {
+            println!(
+                "Reusing cached {} for {}",
+                analysis_type.label(),
+                uuid_filename
+            );
+            return Ok(saved);
+        }
+    }
+
+    let format = match analysis_type {
+        AnalysisType::Builtin(builtin) => builtin.format(),
+        AnalysisType::Custom(_) => None,
+    };
+    let meter = UsageMeter::default();
+    let request = analysis::AnalysisRequest {
+        transcript: input.transcript.clone(),
+        segments: input.segments.as_deref(),
+        system_message: &system_message,
+        format,
+        sink,
+        usage: &meter,
+    };
+    let result = analysis::run_analysis(request).await;
+    record_usage(db, uuid_filename, &analysis_type.kind(), &meter);
+    let output = match result {
+        Ok(output) => output,
+        Err(AnalysisError::Cancelled(partial)) => {
+            save_partial_analysis(
+                db,
+                storage,
+                uuid_filename,
+                analysis_type,
+                &template.version,
+                input.transcript_revision,
+                &partial,
+            )
+            .await;
+            return Err(AnalysisFailure::Cancelled);
+        }
+        Err(e) => {
+            println!("Error generating {}: {}", analysis_type.label(), e);
+            return Err(AnalysisFailure::Model(e));
+        }
+    };
+
+    match analysis_type {
+        AnalysisType::Builtin(builtin) => {
+            let (content, data) = builtin.parse(output).map_err(|e| {
+                println!("Error generating {}: {}", builtin.label(), e);
+                AnalysisFailure::Model(AnalysisError::InvalidOutput(e))
+            })?;
+
+            // Save the text and, for structured analyses, the JSON version of the result
+            let directory = builtin.directory();
+            save_to_file(storage, directory, uuid_filename, &content)
+                .await
+                .map_err(AnalysisFailure::Save)?;
+            record_analysis(
+                db,
+                input,
+                builtin.name(),
+                directory,
+                "txt",
+                &template.version,
+                &cache_key,
+            );
+            if let Some(data) = &data {
+                save_json(storage, directory, uuid_filename, data)
+                    .await
+                    .map_err(AnalysisFailure::Save)?;
+                record_analysis(
+                    db,
+                    input,
+                    &format!("{}_json", directory),
+                    directory,
+                    "json",
+                    &template.version,
+                    &cache_key,
+                );
+            }
+
+            let id = recordings::recording_id_from_filename(uuid_filename);
+            Ok(SavedAnalysis {
+                analysis_id: builtin.name().to_string(),
+                kind: builtin.name().to_string(),
+                file: format!("{}/{}.txt", directory, id),
+                content,
+                data,
+                prompt_version: template.version,
+                cache: CacheInfo {
+                    hit: false,
+                    key: cache_key,
+                    generated_at: Utc::now(),
+                },
+            })
+        }
+        AnalysisType::Custom(_) => {
+            // Custom results never replace each other; each is kept under its own ID together
+            // with the exact prompt it was generated from
+            let id = recordings::recording_id_from_filename(uuid_filename);
+            let analysis_id = Uuid::new_v4().to_string();
+            let directory = format!("analyses/{}", id);
+            save_to_file(storage, &directory, &analysis_id, &output)
+                .await
+                .map_err(AnalysisFailure::Save)?;
+            storage
+                .put(
+                    &format!("{}/{}.prompt.txt", directory, analysis_id),
+                    system_message.into_bytes(),
+                )
+                .await
+                .map_err(AnalysisFailure::Save)?;
+
+            let file = format!("{}/{}.txt", directory, analysis_id);
+            let kind = analysis_type.kind();
+            let mut artifact = recordings::Artifact::new(&kind, &file, Some(analysis::CHAT_MODEL));
+            artifact.prompt_version = Some(template.version.clone());
+            artifact.cache_key = Some(cache_key.clone());
+            artifact.transcript_revision = Some(input.transcript_revision);
+            if let Err(e) = db.add_artifact(id, &artifact) {
+                println!("Could not update recording metadata for {}: {:?}", id, e);
+            }
+
+            Ok(SavedAnalysis {
+                analysis_id,
+                kind,
+                file,
+                content: output,
+                data: None,
+                prompt_version: template.version,
+                cache: CacheInfo {
+                    hit: false,
+                    key: cache_key,
+                    generated_at: artifact.created_at,
+                },
+            })
+        }
+    }
+}
+
+// Keep whatever a stream produced before its client disconnected, without replacing the
+// last complete result
+async fn save_partial_analysis(
+    db: &db::Db,
+    storage: &dyn Storage,
+    uuid_filename: &str,
+    analysis_type: &AnalysisType,
+    prompt_version: &str,
+    transcript_revision: u32,
+    partial: &str,
+) {
+    if partial.trim().is_empty() {
+        return;
+    }
+    let id = recordings::recording_id_from_filename(uuid_filename);
+    let (directory, name) = match analysis_type {
+        AnalysisType::Builtin(builtin) => (builtin.directory().to_string(), id.to_string()),
+        AnalysisType::Custom(_) => (format!("analyses/{}", id), Uuid::new_v4().to_string()),
+    };
+    let file = format!("{}/{}.partial.txt", directory, name);
+    if let Err(e) = storage.put(&file, partial.as_bytes().to_vec()).await {
+        println!("Failed to save partial {}: {:?}", analysis_type.label(), e);
+        return;
+    }
+    println!(
+        "Saved partial {} after the client disconnected: {}",
+        analysis_type.label(),
+        file
+    );
+
+    if !recordings::is_valid_id(id) {
+        return;
+    }
+    let kind = format!("{}_partial", analysis_type.kind());
+    let mut artifact = recordings::Artifact::new(&kind, &file, Some(analysis::CHAT_MODEL));
+    artifact.prompt_version = Some(prompt_version.to_string());
+    artifact.transcript_revision = Some(transcript_revision);
+    let recorded = match analysis_type {
+        AnalysisType::Builtin(_) => db.put_artifact(id, &artifact),
+        AnalysisType::Custom(_) => db.add_artifact(id, &artifact),
+    };
+    if let Err(e) = recorded {
+        println!("Could not update recording metadata for {}: {:?}", id, e);
+    }
+}
+
+fn sse_event(event: &str, data: &serde_json::Value) -> web::Bytes {
+    web::Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+// Run an analysis in the background and stream it to the client as server-sent events:
+// `progress` while parts of a long transcript are processed, `delta` for each piece of
+// model output, `restart` when structured output is requested again, then `done` with the
+// saved analysis or `error`. A client that disconnects stops the analysis.
+fn stream_analysis(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    prompts: web::Data<PromptLibrary>,
+    input: AnalysisInput,
+    analysis_type: AnalysisType,
+    force: bool,
+) -> HttpResponse {
+    let (sender, receiver) = mpsc::unbounded_channel::<web::Bytes>();
+
+    actix_web::rt::spawn(async move {
+        let sink = |event: OutputEvent<'_>| {
+            let frame = match event {
+                OutputEvent::Delta(delta) => sse_event("delta", &json!({ "content": delta })),
+                OutputEvent::Progress(status) => {
+                    sse_event("progress", &json!({ "status": status }))
+                }
+                OutputEvent::Restart => sse_event("restart", &json!({})),
+            };
+            sender.send(frame).is_ok()
+        };
+        let result = run_loaded_analysis(
+            &db,
+            storage.get_ref(),
+            &prompts,
+            &input,
+            &analysis_type,
+            Some(&sink),
+            force,
+        )
+        .await;
+        let frame = match result {
+            Ok(saved) => sse_event("done", &json!(saved)),
+            Err(failure) => sse_event(
+                "error",
+                &json!({ "error": failure.message(&analysis_type) }),
+            ),
+        };
+        let _ = sender.send(frame);
+    });
+
+    let body = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver
+            .recv()
+            .await
+            .map(|frame| (Ok::<_, actix_web::Error>(frame), receiver))
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(body)
+}
+
+// The original per-analysis routes: same request and response shapes as before, or a
+// stream of events with `stream: true`
+async fn builtin_analysis(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    prompts: web::Data<PromptLibrary>,
+    request: web::Json<TranscriptionRequest>,
+    builtin: BuiltinAnalysis,
+) -> HttpResponse {
+    let analysis_type = AnalysisType::Builtin(builtin);
+    if request.stream.unwrap_or(false) {
+        return match load_analysis_input(
+            &db,
+            storage.get_ref(),
+            &request.transcription,
+            request.language.as_deref(),
+        )
+        .await
+        {
+            Ok(input) => stream_analysis(
+                db,
+                storage,
+                prompts,
+                input,
+                analysis_type,
+                request.force.unwrap_or(false),
+            ),
+            Err(_) => HttpResponse::InternalServerError()
+                .json(json!({"error": "Error reading transcription"})),
+        };
+    }
+
+    match run_saved_analysis(
+        &db,
+        storage.get_ref(),
+        &prompts,
+        &request.transcription,
+        &analysis_type,
+        request.language.as_deref(),
+        request.force.unwrap_or(false),
+    )
+    .await
+    {
+        Ok(saved) => {
+            let mut body = json!({
+                "content": saved.content,
+                "prompt_version": saved.prompt_version,
+                "cache": saved.cache
+            });
+            if let Some(data) = saved.data {
+                body["data"] = data;
+            }
+            HttpResponse::Ok().json(body)
+        }
+        Err(failure) => HttpResponse::build(failure.status())
+            .json(json!({"error": failure.message(&analysis_type)})),
+    }
+}
+
+// Endpoint for generating summary from transcription and returning it
+#[post("/summarize")]
+async fn summarize(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    prompts: web::Data<PromptLibrary>,
+    transcription: web::Json<TranscriptionRequest>,
+) -> impl Responder {
+    builtin_analysis(
+        db,
+        storage,
+        prompts,
+        transcription,
+        BuiltinAnalysis::Summary,
+    )
+    .await
+}
+
+#[post("/key_points")]
+async fn key_points(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    prompts: web::Data<PromptLibrary>,
+    transcription: web::Json<TranscriptionRequest>,
+) -> impl Responder {
+    builtin_analysis(
+        db,
+        storage,
+        prompts,
+        transcription,
+        BuiltinAnalysis::KeyPoints,
+    )
+    .await
+}
+
+// Endpoint for extracting action items from transcription
+#[post("/action_items")]
+async fn action_items(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    prompts: web::Data<PromptLibrary>,
+    transcription: web::Json<TranscriptionRequest>,
+) -> impl Responder {
+    builtin_analysis(
+        db,
+        storage,
+        prompts,
+        transcription,
+        BuiltinAnalysis::ActionItems,
+    )
+    .await
+}
+
+// Endpoint for extracting participants from transcription
+#[post("/participants")]
+async fn participants(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    prompts: web::Data<PromptLibrary>,
+    transcription: web::Json<TranscriptionRequest>,
+) -> impl Responder {
+    builtin_analysis(
+        db,
+        storage,
+        prompts,
+        transcription,
+        BuiltinAnalysis::Participants,
+    )
+    .await
+}
+
+// Either `type` (a built-in analysis or a template name) or ad-hoc `instructions`
+#[derive(Deserialize)]
+struct CreateAnalysisRequest {
+    #[serde(rename = "type")]
+    analysis_type: Option<String>,
+    instructions: Option<String>,
+    language: Option<String>,
+    stream: Option<bool>,
+    force: Option<bool>,
+}
+
+fn resolve_analysis_type(
+    prompts: &PromptLibrary,
+    request: &CreateAnalysisRequest,
+) -> Result<AnalysisType, String> {
+    match (&request.analysis_type, &request.instructions) {
+        (Some(name), None) => {
+            if let Some(builtin) = BuiltinAnalysis::from_name(name) {
+                return Ok(AnalysisType::Builtin(builtin));
+            }
+            prompts
+                .get(name)
+                .map(AnalysisType::Custom)
+                .ok_or_else(|| format!("Unknown analysis type: {}", name))
+        }
+        (None, Some(instructions)) => {
+            if instructions.trim().is_empty() {
+                Err("instructions must not be empty".to_string())
+            } else if instructions.chars().count() > analyses::MAX_INSTRUCTIONS_CHARS {
+                Err(format!(
+                    "instructions must be at most {} characters",
+                    analyses::MAX_INSTRUCTIONS_CHARS
+                ))
+            } else {
+                Ok(AnalysisType::Custom(prompts::PromptTemplate::inline(
+                    instructions,
+                )))
+            }
+        }
+        _ => Err("Provide either type or instructions".to_string()),
+    }
+}
+
+// Run any analysis against a recording's transcript and store the result as its own artifact
+#[post("/recordings/{id}/analyses")]
+async fn create_analysis(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    prompts: web::Data<PromptLibrary>,
+    path: web::Path<String>,
+    request: web::Json<CreateAnalysisRequest>,
+) -> impl Responder {
+    let id = path.into_inner();
+    if !recordings::is_valid_id(&id) {
+        return HttpResponse::BadRequest().json(json!({"error": "Invalid recording id"}));
+    }
+    match db.get_recording(&id) {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return HttpResponse::NotFound().json(json!({"error": "Recording not found"}));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading recording: {}", e)}));
+        }
+    }
+
+    let analysis_type = match resolve_analysis_type(&prompts, &request) {
+        Ok(analysis_type) => analysis_type,
+        Err(e) => return HttpResponse::BadRequest().json(json!({ "error": e })),
+    };
+
+    if request.stream.unwrap_or(false) {
+        return match load_analysis_input(&db, storage.get_ref(), &id, request.language.as_deref())
+            .await
+        {
+            Ok(input) => stream_analysis(
+                db,
+                storage,
+                prompts,
+                input,
+                analysis_type,
+                request.force.unwrap_or(false),
+            ),
+            Err(StorageError::NotFound(_)) => {
+                HttpResponse::NotFound().json(json!({"error": "Transcript not found"}))
+            }
+            Err(_) => HttpResponse::InternalServerError()
+                .json(json!({"error": "Error reading transcription"})),
+        };
+    }
+
+    match run_saved_analysis(
+        &db,
+        storage.get_ref(),
+        &prompts,
+        &id,
+        &analysis_type,
+        request.language.as_deref(),
+        request.force.unwrap_or(false),
+    )
+    .await
+    {
+        Ok(saved) => HttpResponse::Ok().json(saved),
+        Err(AnalysisFailure::Transcript(StorageError::NotFound(_))) => {
+            HttpResponse::NotFound().json(json!({"error": "Transcript not found"}))
+        }
+        Err(failure) => HttpResponse::build(failure.status())
+            .json(json!({"error": failure.message(&analysis_type)})),
+    }
+}
+
+#[derive(Deserialize)]
+struct AnalyzeRequest {
+    transcription: String,
+    kinds: Option<Vec<String>>,
+    language: Option<String>,
+    force: Option<bool>,
+}
+
+// Run several analyses concurrently over one read of the transcript. Each analysis succeeds
+// or fails on its own; failures are reported next to the results that did complete.
+#[post("/analyze")]
+async fn analyze(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    prompts: web::Data<PromptLibrary>,
+    request: web::Json<AnalyzeRequest>,
+) -> impl Responder {
+    let mut kinds: Vec<String> = match &request.kinds {
+        Some(kinds) if !kinds.is_empty() => kinds.clone(),
+        _ => BuiltinAnalysis::ALL
+            .iter()
+            .map(|builtin| builtin.name().to_string())
+            .collect(),
+    };
+    let mut seen = HashSet::new();
+    kinds.retain(|kind| seen.insert(kind.clone()));
+
+    let mut analysis_types = Vec::with_capacity(kinds.len());
+    for kind in &kinds {
+        let analysis_type = match BuiltinAnalysis::from_name(kind) {
+            Some(builtin) => AnalysisType::Builtin(builtin),
+            None => match prompts.get(kind) {
+                Some(template) => AnalysisType::Custom(template),
+                None => {
+                    return HttpResponse::BadRequest()
+                        .json(json!({"error": format!("Unknown analysis type: {}", kind)}));
+                }
+            },
+        };
+        analysis_types.push(analysis_type);
+    }
+
+    let input = match load_analysis_input(
+        &db,
+        storage.get_ref(),
+        &request.transcription,
+        request.language.as_deref(),
+    )
+    .await
+    {
+        Ok(input) => input,
+        Err(StorageError::NotFound(_)) => {
+            return HttpResponse::NotFound().json(json!({"error": "Transcript not found"}));
+        }
+        Err(_) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": "Error reading transcription"}));
+        }
+    };
+
+    let outcomes = join_all(analysis_types.iter().map(|analysis_type| {
+        run_loaded_analysis(
+            &db,
+            storage.get_ref(),
+            &prompts,
+            &input,
+            analysis_type,
+            None,
+            request.force.unwrap_or(false),
+        )
+    }))
+    .await;
+
+    let mut results = serde_json::Map::new();
+    let mut errors = serde_json::Map::new();
+    for ((kind, analysis_type), outcome) in kinds.iter().zip(&analysis_types).zip(outcomes) {
+        match outcome {
+            Ok(saved) => {
+                results.insert(kind.clone(), json!(saved));
+            }
+            Err(failure) => {
+                errors.insert(kind.clone(), json!(failure.message(analysis_type)));
+            }
+        }
+    }
+
+    HttpResponse::Ok().json(json!({
+        "results": results,
+        "errors": errors
+    }))
+}
+
+#[derive(Deserialize)]
+struct AskRequest {
+    question: String,
+    top_k: Option<usize>,
+}
+
+// Longest question accepted by the ask endpoint
+const MAX_QUESTION_CHARS: usize = 1000;
+
+// Answer a question from the transcript segments that best match it, citing the segments used
+#[post("/recordings/{id}/ask")]
+async fn ask(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+    request: web::Json<AskRequest>,
+) -> impl Responder {
+    let id = path.into_inner();
+    if !recordings::is_valid_id(&id) {
+        return HttpResponse::BadRequest().json(json!({"error": "Invalid recording id"}));
+    }
+    let question = request.question.trim();
+    if question.is_empty() || question.chars().count() > MAX_QUESTION_CHARS {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!("question must be between 1 and {} characters", MAX_QUESTION_CHARS)
+        }));
+    }
+
+    match db.get_recording(&id) {
+        Ok(Some(recording)) if recording.transcript.is_some() => {}
+        Ok(Some(_)) => {
+            return HttpResponse::NotFound().json(json!({"error": "Transcript not found"}));
+        }
+        Ok(None) => {
+            return HttpResponse::NotFound().json(json!({"error": "Recording not found"}));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading recording: {}", e)}));
+        }
+    }
+
+    let mut segments = match transcript::load_segments(storage.get_ref(), &id).await {
+        Ok(Some(segments)) if !segments.is_empty() => segments,
+        Ok(_) => {
+            return HttpResponse::Conflict()
+                .json(json!({"error": "Transcript has no timestamped segments to search"}));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading transcript segments: {}", e)}));
+        }
+    };
+
+    apply_speaker_names(&db, &id, &mut segments);
+
+    let top_k = request
+        .top_k
+        .unwrap_or(qa::DEFAULT_TOP_K)
+        .clamp(1, qa::MAX_TOP_K);
+    let retrieved = qa::retrieve(&segments, question, top_k);
+    if retrieved.is_empty() {
+        return HttpResponse::Ok().json(json!({
+            "question": question,
+            "answer": "No part of the transcript matches the question.",
+            "citations": []
+        }));
+    }
+
+    let system_message = qa::instructions(question);
+    let meter = UsageMeter::default();
+    let request = analysis::AnalysisRequest {
+        transcript: qa::render_excerpts(&segments, &retrieved),
+        segments: None,
+        system_message: &system_message,
+        format: Some(qa::ANSWER_FORMAT),
+        sink: None,
+        usage: &meter,
+    };
+    let result = analysis::run_analysis(request).await;
+    record_usage(&db, &id, "ask", &meter);
+    let answer = result.and_then(|output| {
+        serde_json::from_str::<qa::Answer>(&output)
+            .map_err(|e| AnalysisError::InvalidOutput(e.to_string()))
+    });
+    match answer {
+        Ok(answer) => HttpResponse::Ok().json(json!({
+            "question": question,
+            "answer": answer.answer,
+            "citations": qa::citations(&segments, &retrieved, &answer.citations)
+        })),
+        Err(e) => {
+            println!("Error answering question for {}: {}", id, e);
+            HttpResponse::BadGateway()
+                .json(json!({"error": format!("Error answering question: {}", e)}))
+        }
+    }
+}
+
+// Diarized segments of a transcribed recording, with their original labels, or the response
+// to send when there are none
+async fn diarized_segments(
+    db: &db::Db,
+    storage: &dyn Storage,
+    id: &str,
+) -> Result<Vec<TranscriptSegment>, HttpResponse> {
+    if !recordings::is_valid_id(id) {
+        return Err(HttpResponse::BadRequest().json(json!({"error": "Invalid recording id"})));
+    }
+    match db.get_recording(id) {
+        Ok(Some(recording)) if recording.transcript.is_some() => {}
+        Ok(Some(_)) => {
+            return Err(HttpResponse::NotFound().json(json!({"error": "Transcript not found"})));
+        }
+        Ok(None) => {
+            return Err(HttpResponse::NotFound().json(json!({"error": "Recording not found"})));
+        }
+        Err(e) => {
+            return Err(HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading recording: {}", e)})));
+        }
+    }
+    match transcript::load_segments(storage, id).await {
+        Ok(Some(segments)) if transcript::has_speakers(&segments) => Ok(segments),
+        Ok(_) => Err(HttpResponse::Conflict()
+            .json(json!({"error": "Transcript has no speaker labels; it was not diarized"}))),
+        Err(e) => Err(HttpResponse::InternalServerError()
+            .json(json!({"error": format!("Error reading transcript segments: {}", e)}))),
+    }
+}
+
+// Rewrite the stored turn-by-turn transcript with the current speaker names
+async fn render_turns(
+    db: &db::Db,
+    storage: &dyn Storage,
+    id: &str,
+    mut segments: Vec<TranscriptSegment>,
+) -> Result<(), StorageError> {
+    apply_speaker_names(db, id, &mut segments);
+    transcript::save_turns(storage, id, &transcript::speaker_turns(&segments)).await
+}
+
+// Speaker labels found by diarization and the names assigned to them
+#[get("/recordings/{id}/speakers")]
+async fn get_speakers(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let id = path.into_inner();
+    let segments = match diarized_segments(&db, storage.get_ref(), &id).await {
+        Ok(segments) => segments,
+        Err(response) => return response,
+    };
+    match db.speakers(&id) {
+        Ok(names) => HttpResponse::Ok().json(json!({
+            "recording_id": id,
+            "speakers": speakers::describe(&segments, &names)
+        })),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(json!({"error": format!("Error reading speakers: {}", e)})),
+    }
+}
+
+#[derive(Deserialize)]
+struct SpeakersRequest {
+    speakers: Vec<speakers::Speaker>,
+}
+
+// Assign names and roles to speaker labels, replacing any earlier assignment. Transcripts
+// and analyses generated afterwards use the names.
+#[put("/recordings/{id}/speakers")]
+async fn put_speakers(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+    request: web::Json<SpeakersRequest>,
+) -> impl Responder {
+    let id = path.into_inner();
+    let segments = match diarized_segments(&db, storage.get_ref(), &id).await {
+        Ok(segments) => segments,
+        Err(response) => return response,
+    };
+
+    let labels = speakers::labels(&segments);
+    let mut names: Vec<speakers::Speaker> = Vec::new();
+    for speaker in &request.speakers {
+        if !labels.contains(&speaker.label) {
+            return HttpResponse::BadRequest().json(json!({
+                "error": format!("Unknown speaker label: {}", speaker.label),
+                "labels": labels
+            }));
+        }
+        if names.iter().any(|n| n.label == speaker.label) {
+            return HttpResponse::BadRequest().json(json!({
+                "error": format!("Speaker label assigned twice: {}", speaker.label)
+            }));
+        }
+        let name = speaker.name.trim();
+        let role = speaker
+            .role
+            .as_deref()
+            .map(str::trim)
+            .filter(|r| !r.is_empty());
+        if name.is_empty()
+            || name.chars().count() > speakers::MAX_NAME_CHARS
+            || role.is_some_and(|r| r.chars().count() > speakers::MAX_NAME_CHARS)
+        {
+            return HttpResponse::BadRequest().json(json!({
+                "error": format!(
+                    "name must be between 1 and {} characters, role at most {}",
+                    speakers::MAX_NAME_CHARS, speakers::MAX_NAME_CHARS
+                )
+            }));
+        }
+        names.push(speakers::Speaker {
+            label: speaker.label.clone(),
+            name: name.to_string(),
+            role: role.map(str::to_string),
+        });
+    }
+
+    if let Err(e) = db.set_speakers(&id, &names) {
+        return HttpResponse::InternalServerError()
+            .json(json!({"error": format!("Error saving speakers: {}", e)}));
+    }
+    if let Err(e) = render_turns(&db, storage.get_ref(), &id, segments.clone()).await {
+        println!("Failed to update speaker turns for {}: {:?}", id, e);
+    }
+    HttpResponse::Ok().json(json!({
+        "recording_id": id,
+        "speakers": speakers::describe(&segments, &names)
+    }))
+}
+
+#[derive(Deserialize)]
+struct SuggestSpeakersRequest {
+    apply: Option<bool>,
+}
+
+// Suggest names for speaker labels from the participants extraction. Suggestions are only
+// saved with `apply: true`, and then only for labels that have no name yet.
+#[post("/recordings/{id}/speakers/suggest")]
+async fn suggest_speakers(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    prompts: web::Data<PromptLibrary>,
+    path: web::Path<String>,
+    request: Option<web::Json<SuggestSpeakersRequest>>,
+) -> impl Responder {
+    let id = path.into_inner();
+    let segments = match diarized_segments(&db, storage.get_ref(), &id).await {
+        Ok(segments) => segments,
+        Err(response) => return response,
+    };
+
+    // Reuses a stored participants extraction when the transcript has not changed
+    let extraction = AnalysisType::Builtin(BuiltinAnalysis::Participants);
+    let extracted = match run_saved_analysis(
+        &db,
+        storage.get_ref(),
+        &prompts,
+        &id,
+        &extraction,
+        None,
+        false,
+    )
+    .await
+    {
+        Ok(saved) => saved,
+        Err(failure) => {
+            return HttpResponse::build(failure.status())
+                .json(json!({"error": failure.message(&extraction)}))
+        }
+    };
+
+    let system_message = speakers::suggestion_instructions(&extracted.content);
+    let meter = UsageMeter::default();
+    let request_body = analysis::AnalysisRequest {
+        transcript: speakers::sample_text(&segments),
+        segments: None,
+        system_message: &system_message,
+        format: Some(SpeakerAssignments::FORMAT),
+        sink: None,
+        usage: &meter,
+    };
+    let result = analysis::run_analysis(request_body).await;
+    record_usage(&db, &id, "speakers", &meter);
+    let suggested = match result.and_then(|output| {
+        serde_json::from_str::<SpeakerAssignments>(&output)
+            .map_err(|e| AnalysisError::InvalidOutput(e.to_string()))
+    }) {
+        Ok(suggested) => suggested,
+        Err(e) => {
+            println!("Error suggesting speakers for {}: {}", id, e);
+            return HttpResponse::BadGateway()
+                .json(json!({"error": format!("Error suggesting speakers: {}", e)}));
+        }
+    };
+
+    // Only labels that exist, each once, and only with a name
+    let labels = speakers::labels(&segments);
+    let mut suggestions: Vec<speakers::Speaker> = Vec::new();
+    for assignment in suggested.speakers {
+        let Some(name) = assignment.name.filter(|n| !n.trim().is_empty()) else {
+            continue;
+        };
+        if labels.contains(&assignment.label)
+            && !suggestions.iter().any(|s| s.label == assignment.label)
+        {
+            suggestions.push(speakers::Speaker {
+                label: assignment.label,
+                name: name.trim().to_string(),
+                role: assignment.role.filter(|r| !r.trim().is_empty()),
+            });
+        }
+    }
+
+    let mut names = match db.speakers(&id) {
+        Ok(names) => names,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading speakers: {}", e)}))
+        }
+    };
+    let apply = request.and_then(|r| r.apply).unwrap_or(false);
+    if apply {
+        for suggestion in &suggestions {
+            if !names.iter().any(|n| n.label == suggestion.label) {
+                names.push(suggestion.clone());
+            }
+        }
+        if let Err(e) = db.set_speakers(&id, &names) {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error saving speakers: {}", e)}));
+        }
+        if let Err(e) = render_turns(&db, storage.get_ref(), &id, segments.clone()).await {
+            println!("Failed to update speaker turns for {}: {:?}", id, e);
+        }
+    }
+
+    HttpResponse::Ok().json(json!({
+        "recording_id": id,
+        "applied": apply,
+        "suggestions": suggestions,
+        "speakers": speakers::describe(&segments, &names)
+    }))
+}
+
+// A recording's transcript segments with the revisions they went through
+struct EditableTranscript {
+    id: String,
+    recording: recordings::Recording,
+    segments: Vec<TranscriptSegment>,
+    // Never empty: until the first edit, the transcript as transcribed is revision 1
+    revisions: Vec<revisions::Revision>,
+}
+
+impl EditableTranscript {
+    fn current(&self) -> &revisions::Revision {
+        self.revisions
+            .last()
+            .expect("revision history is never empty")
+    }
+
+    fn find(&self, revision: u32) -> Result<&revisions::Revision, HttpResponse> {
+        self.revisions
+            .iter()
+            .find(|r| r.revision == revision)
+            .ok_or_else(|| {
+                HttpResponse::NotFound().json(json!({
+                    "error": format!("Revision {} not found", revision),
+                    "current_revision": self.current().revision
+                }))
+            })
+    }
+
+    async fn segments_at(
+        &self,
+        storage: &dyn Storage,
+        revision: &revisions::Revision,
+    ) -> Result<Vec<TranscriptSegment>, HttpResponse> {
+        if revision.revision == self.current().revision {
+            return Ok(self.segments.clone());
+        }
+        let parsed = match storage.get(&revision.file).await {
+            Ok(contents) => {
+                serde_json::from_slice(&contents).map_err(|e| StorageError::Backend(e.to_string()))
+            }
+            Err(e) => Err(e),
+        };
+        parsed.map_err(|e| {
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Error reading revision {}: {}", revision.revision, e)
+            }))
+        })
+    }
+}
+
+async fn editable_transcript(
+    db: &db::Db,
+    storage: &dyn Storage,
+    id: &str,
+) -> Result<EditableTranscript, HttpResponse> {
+    let id = recordings::recording_id_from_filename(id);
+    if !recordings::is_valid_id(id) {
+        return Err(HttpResponse::BadRequest().json(json!({"error": "Invalid recording id"})));
+    }
+    let recording = match db.get_recording(id) {
+        Ok(Some(recording)) => recording,
+        Ok(None) => {
+            return Err(HttpResponse::NotFound().json(json!({"error": "Recording not found"})));
+        }
+        Err(e) => {
+            return Err(HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading recording: {}", e)})));
+        }
+    };
+    let Some(transcript_artifact) = &recording.transcript else {
+        return Err(HttpResponse::NotFound().json(json!({"error": "Transcript not found"})));
+    };
+    let segments = match transcript::load_segments(storage, id).await {
+        Ok(Some(segments)) => segments,
+        Ok(None) => {
+            return Err(HttpResponse::Conflict().json(
+                json!({"error": "Transcript has no stored segments, so it cannot be edited"}),
+            ));
+        }
+        Err(e) => {
+            return Err(HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading transcript segments: {}", e)})));
+        }
+    };
+    let mut history = match db.transcript_revisions(id) {
+        Ok(history) => history,
+        Err(e) => {
+            return Err(HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading revisions: {}", e)})));
+        }
+    };
+    if history.is_empty() {
+        history.push(revisions::Revision {
+            revision: 1,
+            author: revisions::ORIGINAL_AUTHOR.to_string(),
+            comment: None,
+            changed_segments: 0,
+            file: transcript::segments_key(id),
+            created_at: transcript_artifact.created_at,
+        });
+    }
+    Ok(EditableTranscript {
+        id: id.to_string(),
+        recording,
+        segments,
+        revisions: history,
+    })
+}
+
+#[derive(Deserialize)]
+struct TranscriptQuery {
+    revision: Option<u32>,
+}
+
+// A transcript's segments as they are now or at an earlier revision. Segment positions in
+// the list are the indexes edits refer to.
+#[get("/transcripts/{id}")]
+async fn get_transcript(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+    query: web::Query<TranscriptQuery>,
+) -> impl Responder {
+    let transcript = match editable_transcript(&db, storage.get_ref(), &path).await {
+        Ok(transcript) => transcript,
+        Err(response) => return response,
+    };
+    let revision = match query.revision {
+        Some(number) => match transcript.find(number) {
+            Ok(revision) => revision,
+            Err(response) => return response,
+        },
+        None => transcript.current(),
+    };
+    let segments = match transcript.segments_at(storage.get_ref(), revision).await {
+        Ok(segments) => segments,
+        Err(response) => return response,
+    };
+    HttpResponse::Ok().json(json!({
+        "recording_id": transcript.id,
+        "revision": revision,
+        "current_revision": transcript.current().revision,
+        "text": transcript::plain_text(&segments),
+        "segments": segments
+    }))
+}
+
+#[derive(Deserialize)]
+struct EditTranscriptRequest {
+    author: String,
+    comment: Option<String>,
+    // Revision the edits were made against; the edit is refused if the transcript has moved on
+    base_revision: Option<u32>,
+    segments: Vec<revisions::SegmentEdit>,
+}
+
+// Correct transcript segments, saving the result as a new revision. Analyses generated
+// from earlier revisions are reported as stale.
+#[patch("/transcripts/{id}")]
+async fn edit_transcript(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+    request: web::Json<EditTranscriptRequest>,
+) -> impl Responder {
+    let transcript = match editable_transcript(&db, storage.get_ref(), &path).await {
+        Ok(transcript) => transcript,
+        Err(response) => return response,
+    };
+    let id = transcript.id.as_str();
+
+    let author = request.author.trim();
+    if author.is_empty() || author.chars().count() > revisions::MAX_AUTHOR_CHARS {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!("author must be between 1 and {} characters", revisions::MAX_AUTHOR_CHARS)
+        }));
+    }
+    let comment = request
+        .comment
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty());
+    if comment.is_some_and(|c| c.chars().count() > revisions::MAX_SEGMENT_CHARS) {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!("comment must be at most {} characters", revisions::MAX_SEGMENT_CHARS)
+        }));
+    }
+    if request.segments.is_empty() {
+        return HttpResponse::BadRequest().json(json!({"error": "No segment edits given"}));
+    }
+
+    let current = transcript.current().revision;
+    if request.base_revision.is_some_and(|base| base != current) {
+        return HttpResponse::Conflict().json(json!({
+            "error": "The transcript has been edited since that revision",
+            "current_revision": current
+        }));
+    }
+
+    let mut segments = transcript.segments.clone();
+    let labels = speakers::labels(&segments);
+    let changed = match revisions::apply_edits(&mut segments, &request.segments, &labels) {
+        Ok(0) => {
+            return HttpResponse::BadRequest()
+                .json(json!({"error": "The edits do not change the transcript"}))
+        }
+        Ok(changed) => changed,
+        Err(e) => return HttpResponse::BadRequest().json(json!({"error": e})),
+    };
+
+    // The first edit also keeps the transcript as transcribed, as revision 1
+    let revision = revisions::Revision {
+        revision: current + 1,
+        author: author.to_string(),
+        comment: comment.map(str::to_string),
+        changed_segments: changed,
+        file: revisions::revision_key(id, current + 1),
+        created_at: Utc::now(),
+    };
+    let mut claimed = Vec::new();
+    let mut snapshots = Vec::new();
+    if current == 1 && transcript.current().file == transcript::segments_key(id) {
+        let mut original = transcript.current().clone();
+        original.file = revisions::revision_key(id, 1);
+        snapshots.push((original.file.clone(), transcript.segments.clone()));
+        claimed.push(original);
+    }
+    snapshots.push((revision.file.clone(), segments.clone()));
+    claimed.push(revision.clone());
+
+    match db.add_revisions(id, &claimed) {
+        Ok(()) => {}
+        Err(rusqlite::Error::SqliteFailure(e, _))
+            if e.code == rusqlite::ErrorCode::ConstraintViolation =>
+        {
+            return HttpResponse::Conflict().json(json!({
+                "error": "The transcript was edited by someone else at the same time"
+            }));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error saving revision: {}", e)}))
+        }
+    }
+
+    // Revision snapshots first, then the current transcript that everything else reads
+    let mut saved: Result<(), StorageError> = Ok(());
+    for (key, snapshot) in &snapshots {
+        saved = match serde_json::to_vec(snapshot) {
+            Ok(contents) => storage.put(key, contents).await,
+            Err(e) => Err(StorageError::Backend(e.to_string())),
+        };
+        if saved.is_err() {
+            break;
+        }
+    }
+    if saved.is_ok() {
+        saved = transcript::save_segments(storage.get_ref(), id, &segments).await;
+    }
+    if saved.is_ok() {
+        saved = storage
+            .put(
+                &format!("transcriptions/{}.txt", id),
+                transcript::plain_text(&segments).into_bytes(),
+            )
+            .await;
+    }
+    if let Err(e) = saved {
+        let numbers: Vec<u32> = claimed.iter().map(|r| r.revision).collect();
+        if let Err(e) = db.remove_revisions(id, &numbers) {
+            println!("Failed to release revisions of {}: {:?}", id, e);
+        }
+        return HttpResponse::InternalServerError()
+            .json(json!({"error": format!("Error saving transcript: {}", e)}));
+    }
+    if transcript::has_speakers(&segments) {
+        if let Err(e) = render_turns(&db, storage.get_ref(), id, segments.clone()).await {
+            println!("Failed to update speaker turns for {}: {:?}", id, e);
+        }
+    }
+
+    // Analyses generated from an earlier revision, by the same rule as db::load_recording
+    let stale: Vec<recordings::Artifact> = transcript
+        .recording
+        .analyses
+        .into_iter()
+        .filter(|analysis| analysis.transcript_revision.unwrap_or(1) < revision.revision)
+        .map(|mut analysis| {
+            analysis.stale = true;
+            analysis
+        })
+        .collect();
+    HttpResponse::Ok().json(json!({
+        "recording_id": id,
+        "revision": revision,
+        "stale_analyses": stale
+    }))
+}
+
+// Every revision of a transcript, oldest first
+#[get("/transcripts/{id}/revisions")]
+async fn list_transcript_revisions(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+) -> impl Responder {
+    match editable_transcript(&db, storage.get_ref(), &path).await {
+        Ok(transcript) => HttpResponse::Ok().json(json!({
+            "recording_id": transcript.id,
+            "current_revision": transcript.current().revision,
+            "revisions": transcript.revisions
+        })),
+        Err(response) => response,
+    }
+}
+
+#[derive(Deserialize)]
+struct TranscriptDiffQuery {
+    // Defaults to the revision before `to`
+    from: Option<u32>,
+    // Defaults to the current revision
+    to: Option<u32>,
+}
+
+// Segments that differ between two revisions of a transcript
+#[get("/transcripts/{id}/diff")]
+async fn transcript_diff(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+    query: web::Query<TranscriptDiffQuery>,
+) -> impl Responder {
+    let transcript = match editable_transcript(&db, storage.get_ref(), &path).await {
+        Ok(transcript) => transcript,
+        Err(response) => return response,
+    };
+    let to = query.to.unwrap_or(transcript.current().revision);
+    let from = query.from.unwrap_or(to.saturating_sub(1).max(1));
+    let (from, to) = match (transcript.find(from), transcript.find(to)) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(response), _) | (_, Err(response)) => return response,
+    };
+    let before = match transcript.segments_at(storage.get_ref(), from).await {
+        Ok(segments) => segments,
+        Err(response) => return response,
+    };
+    let after = match transcript.segments_at(storage.get_ref(), to).await {
+        Ok(segments) => segments,
+        Err(response) => return response,
+    };
+    HttpResponse::Ok().json(json!({
+        "recording_id": transcript.id,
+        "from": from.revision,
+        "to": to.revision,
+        "changes": revisions::diff(&before, &after)
+    }))
+}
+
+// Fetch one stored analysis by type name (built-in) or analysis ID (custom)
+#[get("/recordings/{id}/analyses/{analysis_id}")]
+async fn get_analysis(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (id, analysis_id) = path.into_inner();
+    if !recordings::is_valid_id(&id) {
+        return HttpResponse::BadRequest().json(json!({"error": "Invalid recording id"}));
+    }
+
+    let builtin = BuiltinAnalysis::from_name(&analysis_id);
+    let file = match builtin {
+        Some(builtin) => format!("{}/{}.txt", builtin.directory(), id),
+        None if recordings::is_valid_id(&analysis_id) => {
+            format!("analyses/{}/{}.txt", id, analysis_id)
+        }
+        None => return HttpResponse::BadRequest().json(json!({"error": "Invalid analysis id"})),
+    };
+
+    let recording = match db.get_recording(&id) {
+        Ok(Some(recording)) => recording,
+        Ok(None) => return HttpResponse::NotFound().json(json!({"error": "Recording not found"})),
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading recording: {}", e)}));
+        }
+    };
+
+    let content = match storage.get(&file).await {
+        Ok(content) => String::from_utf8_lossy(&content).into_owned(),
+        Err(StorageError::NotFound(_)) => {
+            return HttpResponse::NotFound().json(json!({"error": "Analysis not found"}));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading analysis: {}", e)}));
+        }
+    };
+
+    let mut body = json!({
+        "analysis_id": analysis_id,
+        "file": file,
+        "content": content,
+    });
+    if let Some(artifact) = recording.analyses.iter().find(|a| a.file == file) {
+        body["kind"] = json!(artifact.kind);
+        body["model"] = json!(artifact.model);
+        body["prompt_version"] = json!(artifact.prompt_version);
+        body["created_at"] = json!(artifact.created_at);
+    }
+    if let Some(builtin) = builtin.filter(|b| b.format().is_some()) {
+        let json_file = format!("{}/{}.json", builtin.directory(), id);
+        if let Ok(data) = storage.get(&json_file).await {
+            if let Ok(data) = serde_json::from_slice::<serde_json::Value>(&data) {
+                body["data"] = data;
+            }
+        }
+    }
+    HttpResponse::Ok().json(body)
+}
+
+#[derive(Deserialize)]
+struct UploadQuery {
+    title: Option<String>,
+    tags: Option<String>,
+    // Comma separated names and terms to bias transcription towards, on top of the
+    // organization's vocabulary
+    vocabulary: Option<String>,
+}
+
+// Write the multipart payload to `file_path`. A malformed or aborted upload is the client's
+// error; failing to write it is ours.
+async fn save_upload(payload: &mut Multipart, file_path: &str) -> Result<(), HttpResponse> {
+    let write_error = |e: String| {
+        HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error saving upload: {}", e) }))
+    };
+
+    // Clone file_path for use inside web::block to avoid lifetime issues
+    let file_path_clone = file_path.to_string();
+    let mut file = match web::block(move || File::create(&file_path_clone)).await {
+        Ok(Ok(file)) => file,
+        Ok(Err(e)) => return Err(write_error(e.to_string())),
+        Err(e) => return Err(write_error(e.to_string())),
+    };
+
+    // Process each field in the multipart payload
+    while let Some(item) = payload.next().await {
+        let mut field = item.map_err(|e| {
+            HttpResponse::BadRequest()
+                .json(json!({ "error": format!("Invalid multipart upload: {}", e) }))
+        })?;
+
+        // Process the field stream
+        while let Some(chunk) = field.next().await {
+            let data = chunk.map_err(|e| {
+                HttpResponse::BadRequest()
+                    .json(json!({ "error": format!("Upload was interrupted: {}", e) }))
+            })?;
+
+            // Write the chunk to the file
+            file = match web::block(move || {
+                file.write_all(&data)?;
+                Ok::<_, std::io::Error>(file)
+            })
+            .await
+            {
+                Ok(Ok(file)) => file,
+                Ok(Err(e)) => return Err(write_error(e.to_string())),
+                Err(e) => return Err(write_error(e.to_string())),
+            };
+        }
+    }
+    Ok(())
+}
+
+#[post("/upload")]
+async fn upload_audio(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    query: web::Query<UploadQuery>,
+    mut payload: Multipart,
+) -> impl Responder {
+    let request_terms = query
+        .vocabulary
+        .as_deref()
+        .map(vocabulary::parse_terms)
+        .unwrap_or_default();
+    if let Err(e) = vocabulary::validate(&request_terms) {
+        return HttpResponse::BadRequest().json(json!({ "error": e }));
+    }
+    let organization_terms = match db.vocabulary() {
+        Ok(terms) => terms,
+        Err(e) => {
+            println!("Could not read the vocabulary: {:?}", e);
+            Vec::new()
+        }
+    };
+    let terms = vocabulary::merge(&request_terms, &organization_terms);
+    let corrector = match db.correction_rules() {
+        Ok(rules) => corrections::Corrector::new(&rules),
+        Err(e) => {
+            println!("Could not read the correction dictionary: {:?}", e);
+            corrections::Corrector::new(&[])
+        }
+    };
+
+    // The upload UUID identifies the recording and every artifact derived from it
+    let uuid = Uuid::new_v4();
+    let recording_id = uuid.to_string();
+    let upload_key = format!("uploads/{}.mp3", uuid);
+
+    // ffmpeg needs a local file, so the upload is kept in the work directory while it is processed
+    let file_path = format!("{}/{}.mp3", WORK_DIR, uuid);
+
+    // Save the uploaded file
+    if let Err(response) = save_upload(&mut payload, &file_path).await {
+        let _ = fs::remove_file(&file_path).await;
+        return response;
+    }
+
+    if let Err(e) = storage.put_file(&upload_key, Path::new(&file_path)).await {
+        let _ = fs::remove_file(&file_path).await;
+        return HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error storing upload: {}", e) }));
+    }
+
+    // Register the recording before transcription starts
+    let mut recording = recordings::Recording::new(&recording_id);
+    recording.title = query.title.clone();
+    recording.tags = query
+        .tags
+        .as_deref()
+        .map(recordings::parse_tags)
+        .unwrap_or_default();
+    recording.upload = Some(recordings::Artifact::new("upload", &upload_key, None));
+    if let Err(e) = db.insert_recording(&recording) {
+        let _ = fs::remove_file(&file_path).await;
+        return HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error saving recording: {}", e) }));
+    }
+
+    let duration_path = file_path.clone();
+    let duration_secs = web::block(move || audio_processing::get_audio_duration(&duration_path))
+        .await
+        .ok()
+        .and_then(|d| d.ok());
+
+    // Call the transcription process using the UUID filename
+    let meter = Arc::new(UsageMeter::default());
+    let result = process_audio_file(
+        storage.get_ref(),
+        file_path.clone(),
+        &recording_id,
+        &terms,
+        &corrector,
+        &meter,
+    )
+    .await;
+    record_usage(&db, &recording_id, "transcription", &meter);
+    // The playback copy and waveform are made while the upload is still local; their
+    // endpoints create them later if this fails
+    if let Err(e) = store_rendition(&db, storage.get_ref(), &recording_id, &file_path).await {
+        println!(
+            "Failed to create playback rendition for {}: {}",
+            recording_id, e
+        );
+    }
+    if let Err(e) = store_waveform(&db, storage.get_ref(), &recording_id, &file_path).await {
+        println!("Failed to create waveform for {}: {}", recording_id, e);
+    }
+    if let Err(e) = fs::remove_file(&file_path).await {
+        println!("Failed to remove working copy {}: {:?}", file_path, e);
+    }
+
+    match result {
+        Ok(processed) => {
+            let transcript = recordings::Artifact::new(
+                "transcript",
+                &format!("transcriptions/{}", processed.transcription_filename),
+                Some(audio_processing::TRANSCRIPTION_MODEL),
+            );
+            let mut update = db.put_artifact(&recording_id, &transcript);
+            if processed.diarized {
+                let turns =
+                    recordings::Artifact::new("turns", &transcript::turns_key(&recording_id), None);
+                update = update.and_then(|_| db.put_artifact(&recording_id, &turns));
+            }
+            if processed.corrections > 0 || !processed.skipped_rules.is_empty() {
+                let raw = recordings::Artifact::new(
+                    "raw_transcript",
+                    &corrections::raw_segments_key(&recording_id),
+                    Some(audio_processing::TRANSCRIPTION_MODEL),
+                );
+                let applied = recordings::Artifact::new(
+                    "corrections",
+                    &corrections::corrections_key(&recording_id),
+                    None,
+                );
+                update = update
+                    .and_then(|_| db.put_artifact(&recording_id, &raw))
+                    .and_then(|_| db.put_artifact(&recording_id, &applied));
+            }
+            if let Err(e) =
+                update.and_then(|_| db.set_status(&recording_id, "transcribed", duration_secs))
+            {
+                println!("Failed to update recording {}: {:?}", recording_id, e);
+            }
+
+            // Return a JSON response instead of plain text
+            HttpResponse::Ok().json(serde_json::json!({
+                "recording_id": recording_id,
+                "uploaded_file": upload_key,
+                "transcription_file": processed.transcription_filename,
+                "diarized": processed.diarized,
+                "corrections": processed.corrections,
+                "skipped_correction_rules": processed.skipped_rules
+            }))
+        }
+        Err(e) => {
+            if let Err(update_err) = db.set_status(&recording_id, "failed", duration_secs) {
+                println!(
+                    "Failed to update recording {}: {:?}",
+                    recording_id, update_err
+                );
+            }
+
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Error: {}", e) }))
+        }
+    }
+}
+
+// Return a recording with its upload, transcript and all analyses
+#[get("/recordings/{id}")]
+async fn get_recording(db: web::Data<db::Db>, path: web::Path<String>) -> impl Responder {
+    let id = path.into_inner();
+    if !recordings::is_valid_id(&id) {
+        return HttpResponse::BadRequest().json(json!({"error": "Invalid recording id"}));
+    }
+
+    match db.get_recording(&id) {
+        Ok(Some(recording)) => HttpResponse::Ok().json(recording),
+        Ok(None) => HttpResponse::NotFound().json(json!({"error": "Recording not found"})),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(json!({"error": format!("Error reading recording: {}", e)})),
+    }
+}
+
+#[derive(Deserialize)]
+struct DeleteRecordingQuery {
+    // Delete even while the recording is marked as processing, e.g. when processing was
+    // interrupted by a restart and will never finish
+    force: Option<bool>,
+}
+
+// Permanently remove a recording and everything derived from it
+#[delete("/recordings/{id}")]
+async fn delete_recording(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+    query: web::Query<DeleteRecordingQuery>,
+) -> impl Responder {
+    let id = path.into_inner();
+    if !recordings::is_valid_id(&id) {
+        return HttpResponse::BadRequest().json(json!({"error": "Invalid recording id"}));
+    }
+
+    let recording = match db.get_recording(&id) {
+        Ok(Some(recording)) => recording,
+        Ok(None) => return HttpResponse::NotFound().json(json!({"error": "Recording not found"})),
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading recording: {}", e)}))
+        }
+    };
+    if recording.status == "processing" && !query.force.unwrap_or(false) {
+        return HttpResponse::Conflict().json(json!({
+            "error": "Recording is still being processed; pass force=true to delete it anyway"
+        }));
+    }
+
+    match deletion::delete_recording(&db, storage.get_ref(), &recording).await {
+        Ok(audit) => HttpResponse::Ok().json(audit),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(json!({"error": format!("Error deleting recording: {}", e)})),
+    }
+}
+
+#[derive(Deserialize)]
+struct ListRecordingsQuery {
+    from: Option<String>,
+    to: Option<String>,
+    status: Option<String>,
+    q: Option<String>,
+    tags: Option<String>,
+    page: Option<String>,
+    limit: Option<usize>,
+}
+
+// Accept either a full RFC 3339 timestamp or a plain date; `end_of_day` moves dates to the next midnight
+fn parse_date_param(value: &str, end_of_day: bool) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let date = if end_of_day { date.succ_opt()? } else { date };
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+// List recordings newest first with optional filters and cursor pagination
+#[get("/recordings")]
+async fn list_recordings(
+    db: web::Data<db::Db>,
+    query: web::Query<ListRecordingsQuery>,
+) -> impl Responder {
+    let from = match query.from.as_deref().map(|v| parse_date_param(v, false)) {
+        Some(None) => {
+            return HttpResponse::BadRequest().json(json!({"error": "Invalid 'from' date"}))
+        }
+        other => other.flatten(),
+    };
+    let to = match query.to.as_deref().map(|v| parse_date_param(v, true)) {
+        Some(None) => {
+            return HttpResponse::BadRequest().json(json!({"error": "Invalid 'to' date"}))
+        }
+        other => other.flatten(),
+    };
+    let cursor = match query.page.as_deref().map(str::parse::<i64>) {
+        Some(Err(_)) => return HttpResponse::BadRequest().json(json!({"error": "Invalid page"})),
+        other => other.and_then(Result::ok),
+    };
+
+    let filter = db::RecordingFilter {
+        from,
+        to,
+        status: query.status.clone().filter(|s| !s.is_empty()),
+        query: query.q.clone().filter(|q| !q.is_empty()),
+        tags: query
+            .tags
+            .as_deref()
+            .map(recordings::parse_tags)
+            .unwrap_or_default(),
+        cursor,
+        limit: query.limit.unwrap_or(20).clamp(1, 100),
+    };
+
+    match db.list_recordings(&filter) {
+        Ok((recordings, next_cursor)) => HttpResponse::Ok().json(json!({
+            "recordings": recordings,
+            "next_page": next_cursor.map(|c| c.to_string()),
+        })),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(json!({"error": format!("Error listing recordings: {}", e)})),
+    }
+}
+
+#[derive(Deserialize)]
+struct UsageQuery {
+    from: Option<String>,
+    to: Option<String>,
+    recording_id: Option<String>,
+    api_key: Option<String>,
+    group_by: Option<String>,
+}
+
+// Token counts, audio minutes and estimated cost, grouped by recording, API key, model,
+// operation or day
+#[get("/usage")]
+async fn usage_report(
+    db: web::Data<db::Db>,
+    prices: web::Data<PriceTable>,
+    query: web::Query<UsageQuery>,
+) -> impl Responder {
+    let from = match query.from.as_deref().map(|v| parse_date_param(v, false)) {
+        Some(None) => {
+            return HttpResponse::BadRequest().json(json!({"error": "Invalid 'from' date"}))
+        }
+        other => other.flatten(),
+    };
+    let to = match query.to.as_deref().map(|v| parse_date_param(v, true)) {
+        Some(None) => {
+            return HttpResponse::BadRequest().json(json!({"error": "Invalid 'to' date"}))
+        }
+        other => other.flatten(),
+    };
+    let group_name = query.group_by.as_deref().unwrap_or("recording");
+    let Some(group_by) = db::UsageGrouping::from_name(group_name) else {
+        return HttpResponse::BadRequest().json(json!({
+            "error": "group_by must be one of recording, api_key, model, operation, day"
+        }));
+    };
+
+    let filter = db::UsageFilter {
+        from,
+        to,
+        recording_id: query.recording_id.clone().filter(|r| !r.is_empty()),
+        api_key: query.api_key.clone().filter(|k| !k.is_empty()),
+        group_by,
+    };
+    let rows = match db.usage_totals(&filter) {
+        Ok(rows) => rows,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading usage: {}", e)}))
+        }
+    };
+
+    // Rows come ordered by group, one per model; fold them into one entry per group
+    let mut groups: Vec<serde_json::Value> = Vec::new();
+    let mut unpriced: Vec<String> = Vec::new();
+    let mut total = json!({
+        "calls": 0, "prompt_tokens": 0, "completion_tokens": 0,
+        "audio_minutes": 0.0, "estimated_cost": 0.0
+    });
+    for row in rows {
+        let cost = prices.cost(
+            &row.model,
+            row.prompt_tokens,
+            row.completion_tokens,
+            row.audio_seconds,
+        );
+        if cost.is_none() && !unpriced.contains(&row.model) {
+            unpriced.push(row.model.clone());
+        }
+        let model = json!({
+            "model": row.model,
+            "calls": row.calls,
+            "prompt_tokens": row.prompt_tokens,
+            "completion_tokens": row.completion_tokens,
+            "audio_minutes": row.audio_seconds / 60.0,
+            "estimated_cost": cost,
+        });
+
+        if groups
+            .last()
+            .map(|g| g["key"] != json!(row.group))
+            .unwrap_or(true)
+        {
+            groups.push(json!({
+                "key": row.group,
+                "calls": 0, "prompt_tokens": 0, "completion_tokens": 0,
+                "audio_minutes": 0.0, "estimated_cost": 0.0, "models": []
+            }));
+        }
+        let group = groups.last_mut().unwrap();
+        for totals in [&mut *group, &mut total] {
+            totals["calls"] = json!(totals["calls"].as_u64().unwrap_or(0) + row.calls);
+            totals["prompt_tokens"] =
+                json!(totals["prompt_tokens"].as_u64().unwrap_or(0) + row.prompt_tokens);
+            totals["completion_tokens"] =
+                json!(totals["completion_tokens"].as_u64().unwrap_or(0) + row.completion_tokens);
+            totals["audio_minutes"] =
+                json!(totals["audio_minutes"].as_f64().unwrap_or(0.0) + row.audio_seconds / 60.0);
+            totals["estimated_cost"] =
+                json!(totals["estimated_cost"].as_f64().unwrap_or(0.0) + cost.unwrap_or(0.0));
+        }
+        group["models"].as_array_mut().unwrap().push(model);
+    }
+
+    HttpResponse::Ok().json(json!({
+        "group_by": group_name,
+        "currency": prices.currency,
+        "groups": groups,
+        "totals": total,
+        "unpriced_models": unpriced,
+    }))
+}
+
+#[derive(Deserialize)]
+struct TranscriptExportQuery {
+    format: Option<String>,
+    caption: Option<String>,
+}
+
+// The transcript in court format: 25 numbered lines per page under a caption, speaker names
+// in capitals and recording times in the margin, as plain text or PDF
+#[get("/recordings/{id}/export/transcript")]
+async fn export_transcript(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+    query: web::Query<TranscriptExportQuery>,
+) -> impl Responder {
+    let id = path.into_inner();
+    if !recordings::is_valid_id(&id) {
+        return HttpResponse::BadRequest().json(json!({"error": "Invalid recording id"}));
+    }
+    let format = query.format.as_deref().unwrap_or("txt");
+    if format != "txt" && format != "pdf" {
+        return HttpResponse::BadRequest().json(json!({"error": "format must be txt or pdf"}));
+    }
+    let recording = match db.get_recording(&id) {
+        Ok(Some(recording)) => recording,
+        Ok(None) => return HttpResponse::NotFound().json(json!({"error": "Recording not found"})),
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading recording: {}", e)}))
+        }
+    };
+    let transcript = match read_transcription_content(storage.get_ref(), &id).await {
+        Ok(transcript) => transcript,
+        Err(StorageError::NotFound(_)) => {
+            return HttpResponse::NotFound().json(json!({"error": "Transcript not found"}))
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading transcription: {}", e)}))
+        }
+    };
+    let segments = read_transcript_segments(&db, storage.get_ref(), &id).await;
+
+    let caption = court::Caption {
+        title: query
+            .caption
+            .as_deref()
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(str::to_string)
+            .or(recording.title)
+            .unwrap_or_else(|| "Untitled hearing".to_string()),
+        date: format!("Hearing of {}", recording.created_at.format("%Y-%m-%d")),
+    };
+    let pages = court::layout(segments.as_deref(), &transcript);
+    let (content_type, body) = match format {
+        "pdf" => ("application/pdf", court::render_pdf(&caption, &pages)),
+        _ => (
+            "text/plain; charset=utf-8",
+            court::render_text(&caption, &pages).into_bytes(),
+        ),
+    };
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((
+            "Content-Disposition",
+            download::content_disposition(false, &format!("{}-transcript.{}", id, format)),
+        ))
+        .body(body)
+}
+
+// The report section for a built-in analysis from what is stored for the recording
+async fn report_section(
+    storage: &dyn Storage,
+    recording: &recordings::Recording,
+    analysis: BuiltinAnalysis,
+) -> report::Section {
+    let id = recording.id.as_str();
+    let heading = match analysis {
+        BuiltinAnalysis::Summary => "Summary",
+        BuiltinAnalysis::KeyPoints => "Key points",
+        BuiltinAnalysis::ActionItems => "Action items",
+        BuiltinAnalysis::Participants => "Participants",
+    };
+    let directory = analysis.directory();
+    let structured = match analysis.format() {
+        Some(_) => storage
+            .get(&format!("{}/{}.json", directory, id))
+            .await
+            .ok(),
+        None => None,
+    };
+    let content = match structured.and_then(|json| analysis.stored_items(&json)) {
+        Some(items) => report::SectionContent::Items(items),
+        None => match storage.get(&format!("{}/{}.txt", directory, id)).await {
+            Ok(text) => {
+                let text = String::from_utf8_lossy(&text);
+                if analysis.format().is_some() {
+                    report::SectionContent::Items(report::list_items(&text))
+                } else {
+                    report::SectionContent::Paragraphs(report::paragraphs(&text))
+                }
+            }
+            Err(_) => report::SectionContent::Missing,
+        },
+    };
+    // Built-in results are overwritten in place, so the latest artifact describes what is stored
+    let stale = !matches!(content, report::SectionContent::Missing)
+        && recording
+            .analyses
+            .iter()
+            .rev()
+            .find(|artifact| artifact.kind == analysis.name())
+            .is_some_and(|artifact| artifact.stale);
+    report::Section {
+        heading,
+        content,
+        stale,
+    }
+}
+
+#[derive(Deserialize)]
+struct ReportQuery {
+    format: Option<String>,
+}
+
+// One document combining the recording details with its summary, key points, action items
+// and participants, as PDF, DOCX, Markdown or HTML. Analyses that have not been run are
+// listed as not generated rather than run here, and ones made before the latest transcript
+// edit carry a note saying so.
+#[get("/recordings/{id}/report")]
+async fn recording_report(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+    query: web::Query<ReportQuery>,
+) -> impl Responder {
+    let id = path.into_inner();
+    if !recordings::is_valid_id(&id) {
+        return HttpResponse::BadRequest().json(json!({"error": "Invalid recording id"}));
+    }
+    let format = query.format.as_deref().unwrap_or("pdf");
+    if !["pdf", "docx", "md", "html"].contains(&format) {
+        return HttpResponse::BadRequest()
+            .json(json!({"error": "format must be one of pdf, docx, md, html"}));
+    }
+    let recording = match db.get_recording(&id) {
+        Ok(Some(recording)) => recording,
+        Ok(None) => return HttpResponse::NotFound().json(json!({"error": "Recording not found"})),
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading recording: {}", e)}))
+        }
+    };
+
+    let mut details = vec![
+        ("Recording", recording.id.clone()),
+        (
+            "Date",
+            recording
+                .created_at
+                .format("%Y-%m-%d %H:%M UTC")
+                .to_string(),
+        ),
+    ];
+    if let Some(duration) = recording.duration_secs {
+        details.push(("Duration", transcript::format_timestamp(duration as f64)));
+    }
+    if !recording.tags.is_empty() {
+        details.push(("Tags", recording.tags.join(", ")));
+    }
+    details.push((
+        "Generated",
+        Utc::now().format("%Y-%m-%d %H:%M UTC").to_string(),
+    ));
+
+    let mut sections = Vec::new();
+    for analysis in BuiltinAnalysis::ALL {
+        sections.push(report_section(storage.get_ref(), &recording, analysis).await);
+    }
+    let report = report::Report {
+        title: recording
+            .title
+            .clone()
+            .unwrap_or_else(|| "Untitled hearing".to_string()),
+        details,
+        sections,
+    };
+
+    let (content_type, body) = match format {
+        "pdf" => ("application/pdf", report::render_pdf(&report)),
+        "docx" => match report::render_docx(&report) {
+            Ok(body) => (
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+                body,
+            ),
+            Err(e) => {
+                return HttpResponse::InternalServerError()
+                    .json(json!({"error": format!("Error building report: {}", e)}))
+            }
+        },
+        "md" => (
+            "text/markdown; charset=utf-8",
+            report::render_markdown(&report).into_bytes(),
+        ),
+        _ => (
+            "text/html; charset=utf-8",
+            report::render_html(&report).into_bytes(),
+        ),
+    };
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((
+            "Content-Disposition",
+            download::content_disposition(false, &format!("{}-report.{}", id, format)),
+        ))
+        .body(body)
+}
+
+#[derive(Deserialize)]
+struct DownloadQuery {
+    // Display in the browser (e.g. play audio) instead of saving
+    inline: Option<bool>,
+    // Name to save the file as, instead of its storage name
+    filename: Option<String>,
+}
+
+// Download a file from storage, with its MIME type, conditional GET and byte ranges so audio
+// can be streamed into a player
+#[route("/download/{category}/{file_name:.+}", method = "GET", method = "HEAD")]
+async fn download_file(
+    request: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<(String, String)>,
+    query: web::Query<DownloadQuery>,
+) -> impl Responder {
+    let (category, file_name) = path.into_inner();
+    // Only artifact directories are served; staged deletions and other data are not.
+    // Custom analyses are one level down, in a directory named after their recording.
+    let allowed = match file_name.split_once('/') {
+        None => true,
+        Some((recording_id, name)) => {
+            category == "analyses" && recordings::is_valid_id(recording_id) && !name.contains('/')
+        }
+    };
+    if !allowed || !storage::ARTIFACT_DIRS.contains(&category.as_str()) {
+        return HttpResponse::NotFound().body("File not found");
+    }
+    let key = format!("{}/{}", category, file_name);
+
+    let filename = query
+        .filename
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| file_name.rsplit('/').next().unwrap_or(&file_name));
+    download::serve(
+        &request,
+        storage.get_ref(),
+        &key,
+        download::content_type(&key),
+        Some(download::content_disposition(
+            query.inline.unwrap_or(false),
+            filename,
+        )),
+    )
+    .await
+}
+
+// Transcode a local copy of the upload into the playback rendition and record it
+async fn store_rendition(
+    db: &db::Db,
+    storage: &dyn Storage,
+    recording_id: &str,
+    source_path: &str,
+) -> Result<recordings::Artifact, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    // Unique per call, as concurrent player requests may both find the rendition missing
+    let output_path = format!("{}/{}.rendition.mp3", WORK_DIR, Uuid::new_v4());
+    let source = source_path.to_string();
+    let output = output_path.clone();
+    let transcoded =
+        web::block(move || audio_processing::create_rendition(&source, Path::new(&output))).await;
+    let stored = match transcoded {
+        Ok(Ok(())) => {
+            storage
+                .put_file(
+                    &audio_processing::rendition_key(recording_id),
+                    Path::new(&output_path),
+                )
+                .await
+        }
+        Ok(Err(e)) => Err(StorageError::Backend(e.to_string())),
+        Err(e) => Err(StorageError::Backend(e.to_string())),
+    };
+    let _ = fs::remove_file(&output_path).await;
+    stored?;
+
+    let artifact = recordings::Artifact::new(
+        "rendition",
+        &audio_processing::rendition_key(recording_id),
+        None,
+    );
+    db.put_artifact(recording_id, &artifact)?;
+    Ok(artifact)
+}
+
+// Work on a local copy of the stored upload, for recordings processed before an artifact
+// derived from it existed
+async fn with_upload_copy<T, F, Fut>(
+    storage: &dyn Storage,
+    upload: &recordings::Artifact,
+    work: F,
+) -> Result<T, Box<dyn std::error::Error + Send + Sync + 'static>>
+where
+    F: FnOnce(String) -> Fut,
+    Fut:
+        std::future::Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync + 'static>>>,
+{
+    let source_path = format!("{}/{}.source.mp3", WORK_DIR, Uuid::new_v4());
+    let result = match storage::copy_to_file(storage, &upload.file, Path::new(&source_path)).await {
+        Ok(()) => work(source_path.clone()).await,
+        Err(e) => Err(Box::new(e) as Box<dyn std::error::Error + Send + Sync>),
+    };
+    let _ = fs::remove_file(&source_path).await;
+    result
+}
+
+// The playback rendition, created from the stored upload for recordings that predate it
+async fn ensure_rendition(
+    db: &db::Db,
+    storage: &dyn Storage,
+    recording: &recordings::Recording,
+    upload: &recordings::Artifact,
+) -> Result<recordings::Artifact, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    if let Some(rendition) = &recording.rendition {
+        return Ok(rendition.clone());
+    }
+    with_upload_copy(storage, upload, |source_path| async move {
+        store_rendition(db, storage, &recording.id, &source_path).await
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct AudioQuery {
+    // "original" (default) or "compact", a low-bitrate normalised copy for browsers
+    rendition: Option<String>,
+}
+
+// Stream a recording's audio with byte-range support, so the review player can seek to
+// cited timestamps without downloading the whole file
+#[route("/recordings/{id}/audio", method = "GET", method = "HEAD")]
+async fn recording_audio(
+    request: HttpRequest,
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+    query: web::Query<AudioQuery>,
+) -> impl Responder {
+    let id = path.into_inner();
+    if !recordings::is_valid_id(&id) {
+        return HttpResponse::BadRequest().json(json!({"error": "Invalid recording id"}));
+    }
+    let recording = match db.get_recording(&id) {
+        Ok(Some(recording)) => recording,
+        Ok(None) => return HttpResponse::NotFound().json(json!({"error": "Recording not found"})),
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading recording: {}", e)}))
+        }
+    };
+    let Some(upload) = recording.upload.clone() else {
+        return HttpResponse::NotFound().json(json!({"error": "Recording has no audio"}));
+    };
+
+    let artifact = match query.rendition.as_deref().unwrap_or("original") {
+        "original" => upload,
+        "compact" => match ensure_rendition(&db, storage.get_ref(), &recording, &upload).await {
+            Ok(rendition) => rendition,
+            Err(e) => {
+                return HttpResponse::InternalServerError()
+                    .json(json!({"error": format!("Error creating playback rendition: {}", e)}))
+            }
+        },
+        other => {
+            return HttpResponse::BadRequest().json(json!({
+                "error": format!("Unknown rendition '{}'; use original or compact", other)
+            }))
+        }
+    };
+
+    let filename = artifact.file.rsplit('/').next().unwrap_or(&artifact.file);
+    download::serve(
+        &request,
+        storage.get_ref(),
+        &artifact.file,
+        download::content_type(&artifact.file),
+        Some(download::content_disposition(true, filename)),
+    )
+    .await
+}
+
+// Compute peak data from a local copy of the upload and record it
+async fn store_waveform(
+    db: &db::Db,
+    storage: &dyn Storage,
+    recording_id: &str,
+    source_path: &str,
+) -> Result<waveform::Waveform, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let source = source_path.to_string();
+    let peaks = web::block(move || waveform::generate(&source)).await??;
+    let key = waveform::waveform_key(recording_id);
+    storage.put(&key, peaks.to_dat()).await?;
+    db.put_artifact(
+        recording_id,
+        &recordings::Artifact::new("waveform", &key, None),
+    )?;
+    Ok(peaks)
+}
+
+#[derive(Deserialize)]
+struct WaveformQuery {
+    // "json" (default) or "dat", the audiowaveform binary format
+    format: Option<String>,
+    // Coarser resolution for overviews; a multiple of the stored samples per pixel
+    samples_per_pixel: Option<u32>,
+}
+
+// Peak data for the review UI's waveform, in audiowaveform's JSON or binary format
+#[get("/recordings/{id}/waveform")]
+async fn recording_waveform(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+    query: web::Query<WaveformQuery>,
+) -> impl Responder {
+    let id = path.into_inner();
+    if !recordings::is_valid_id(&id) {
+        return HttpResponse::BadRequest().json(json!({"error": "Invalid recording id"}));
+    }
+    let format = query.format.as_deref().unwrap_or("json");
+    if format != "json" && format != "dat" {
+        return HttpResponse::BadRequest()
+            .json(json!({"error": format!("Unsupported format '{}'; use json or dat", format)}));
+    }
+    let factor = match query.samples_per_pixel {
+        None => 1,
+        Some(spp) if spp > 0 && spp % waveform::SAMPLES_PER_PIXEL == 0 => {
+            spp / waveform::SAMPLES_PER_PIXEL
+        }
+        Some(_) => {
+            return HttpResponse::BadRequest().json(json!({
+                "error": format!(
+                    "samples_per_pixel must be a multiple of {}",
+                    waveform::SAMPLES_PER_PIXEL
+                )
+            }))
+        }
+    };
+
+    let recording = match db.get_recording(&id) {
+        Ok(Some(recording)) => recording,
+        Ok(None) => return HttpResponse::NotFound().json(json!({"error": "Recording not found"})),
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading recording: {}", e)}))
+        }
+    };
+
+    let stored = match &recording.waveform {
+        Some(artifact) => match storage.get(&artifact.file).await {
+            Ok(data) => waveform::Waveform::from_dat(&data).map_err(|e| e.into()),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error + Send + Sync>),
+        },
+        None => match &recording.upload {
+            Some(upload) => {
+                let (db, storage) = (db.get_ref(), storage.get_ref());
+                with_upload_copy(storage, upload, |source_path| async move {
+                    store_waveform(db, storage, &id, &source_path).await
+                })
+                .await
+            }
+            None => {
+                return HttpResponse::NotFound().json(json!({"error": "Recording has no audio"}))
+            }
+        },
+    };
+    let peaks = match stored {
+        Ok(peaks) if factor > 1 => peaks.downsample(factor),
+        Ok(peaks) => peaks,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading waveform: {}", e)}))
+        }
+    };
+
+    if format == "dat" {
+        HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .insert_header((
+                "Content-Disposition",
+                download::content_disposition(false, &format!("{}.dat", recording.id)),
+            ))
+            .body(peaks.to_dat())
+    } else {
+        HttpResponse::Ok().json(peaks.to_json())
+    }
+}
+
+#[derive(Deserialize)]
+struct VocabularyRequest {
+    terms: Vec<String>,
+}
+
+// The organization's vocabulary, used to bias every transcription
+#[get("/vocabulary")]
+async fn get_vocabulary(db: web::Data<db::Db>) -> impl Responder {
+    match db.vocabulary() {
+        Ok(terms) => HttpResponse::Ok().json(json!({ "terms": terms })),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(json!({"error": format!("Error reading vocabulary: {}", e)})),
+    }
+}
+
+// Replace the organization's vocabulary. It applies to transcriptions from now on.
+#[put("/vocabulary")]
+async fn put_vocabulary(
+    db: web::Data<db::Db>,
+    request: web::Json<VocabularyRequest>,
+) -> impl Responder {
+    let terms = vocabulary::merge(&request.terms, &[]);
+    if let Err(e) = vocabulary::validate(&terms) {
+        return HttpResponse::BadRequest().json(json!({ "error": e }));
+    }
+    match db.set_vocabulary(&terms) {
+        Ok(()) => HttpResponse::Ok().json(json!({ "terms": terms })),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(json!({"error": format!("Error saving vocabulary: {}", e)})),
+    }
+}
+
+#[derive(Deserialize)]
+struct CorrectionsRequest {
+    rules: Vec<corrections::CorrectionRule>,
+}
+
+// The correction dictionary applied to new transcripts
+#[get("/corrections")]
+async fn get_corrections(db: web::Data<db::Db>) -> impl Responder {
+    match db.correction_rules() {
+        Ok(rules) => HttpResponse::Ok().json(json!({ "rules": rules })),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(json!({"error": format!("Error reading correction dictionary: {}", e)})),
+    }
+}
+
+// Replace the correction dictionary. Rules apply in order to transcripts made from now on.
+#[put("/corrections")]
+async fn put_corrections(
+    db: web::Data<db::Db>,
+    request: web::Json<CorrectionsRequest>,
+) -> impl Responder {
+    if let Err(e) = corrections::validate(&request.rules) {
+        return HttpResponse::BadRequest().json(json!({ "error": e }));
+    }
+    match db.set_correction_rules(&request.rules) {
+        Ok(()) => HttpResponse::Ok().json(json!({ "rules": request.rules })),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(json!({"error": format!("Error saving correction dictionary: {}", e)})),
+    }
+}
+
+// The replacements the correction dictionary made in a transcript, with the segments as
+// they were before
+#[get("/transcripts/{id}/corrections")]
+async fn transcript_corrections(
+    db: web::Data<db::Db>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let id = path.into_inner();
+    let id = recordings::recording_id_from_filename(&id);
+    if !recordings::is_valid_id(id) {
+        return HttpResponse::BadRequest().json(json!({"error": "Invalid recording id"}));
+    }
+    let recording = match db.get_recording(id) {
+        Ok(Some(recording)) => recording,
+        Ok(None) => return HttpResponse::NotFound().json(json!({"error": "Recording not found"})),
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": format!("Error reading recording: {}", e)}))
+        }
+    };
+    if recording.transcript.is_none() {
+        return HttpResponse::NotFound().json(json!({"error": "Transcript not found"}));
+    }
+    let (Some(applied), Some(raw)) = (&recording.corrections, &recording.raw_transcript) else {
+        return HttpResponse::Ok().json(json!({
+            "recording_id": id,
+            "corrections": [],
+            "skipped_rules": [],
+            "raw_segments": null
+        }));
+    };
+
+    let read = |key: String| {
+        let storage = storage.clone();
+        async move {
+            let contents = storage.get(&key).await?;
+            serde_json::from_slice::<serde_json::Value>(&contents)
+                .map_err(|e| StorageError::Backend(e.to_string()))
+        }
+    };
+    let log = read(applied.file.clone()).await.and_then(|log| {
+        serde_json::from_value::<corrections::CorrectionLog>(log)
+            .map_err(|e| StorageError::Backend(e.to_string()))
+    });
+    match (log, read(raw.file.clone()).await) {
+        (Ok(log), Ok(raw)) => HttpResponse::Ok().json(json!({
+            "recording_id": id,
+            "corrections": log.corrections,
+            "skipped_rules": log.skipped_rules,
+            "raw_segments": raw
+        })),
+        (Err(e), _) | (_, Err(e)) => HttpResponse::InternalServerError()
+            .json(json!({"error": format!("Error reading corrections: {}", e)})),
+    }
+}
+
+// List the prompt templates in use with their versions
+#[get("/prompts")]
+async fn list_prompts(prompts: web::Data<PromptLibrary>) -> impl Responder {
+    HttpResponse::Ok().json(json!({ "prompts": prompts.list() }))
+}
+
+#[get("/health")]
+async fn health() -> impl Responder {
+    println!("Health check requested");
+    HttpResponse::Ok().body("Server is running")
+}
+
+// What transcribing an upload produced
+struct ProcessedAudio {
+    transcription_filename: String,
+    // Whether speakers were labelled
+    diarized: bool,
+    // Replacements the correction dictionary made
+    corrections: usize,
+    // Positions of dictionary rules that could not be applied
+    skipped_rules: Vec<usize>,
+}
+
+async fn process_audio_file(
+    storage: &dyn Storage,
+    file_path: String,
+    recording_id: &str,
+    vocabulary: &[String],
+    corrector: &corrections::Corrector,
+    usage: &Arc<UsageMeter>,
+) -> Result<ProcessedAudio, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    println!("Starting transcription process for file: {}", file_path);
+
+    // Load environment variables
+
+    // Get the OpenAI API key from the environment
+    let openai_api_key =
+        std::env::var("OPENAI_API_KEY").map_err(|_| "OPENAI_API_KEY must be set")?;
+
+    // Debug message for starting transcription process
+    println!("API key loaded. Starting the transcription process...");
+
+    // Process and transcribe the audio file using the existing logic
+    let mut segments = audio_processing::split_audio_by_size_and_transcribe(
+        &file_path,
+        1024 * 1024 * 10, // Example max segment size (5MB)
+        &openai_api_key,
+        vocabulary,
+        usage,
+    )
+    .await?;
+
+    // Debug message for checking if transcriptions were received
+    println!("Transcript segments received: {}", segments.len());
+
+    // Fix known misspellings with the correction dictionary, keeping the segments as
+    // transcribed and a list of what was changed
+    let raw_segments = segments.clone();
+    let log = corrections::CorrectionLog {
+        skipped_rules: corrector.skipped().to_vec(),
+        corrections: corrector.apply(&mut segments),
+    };
+    if !log.corrections.is_empty() || !log.skipped_rules.is_empty() {
+        println!(
+            "Correction dictionary made {} replacements, skipped {} rules",
+            log.corrections.len(),
+            log.skipped_rules.len()
+        );
+        let stored = match (
+            serde_json::to_vec(&raw_segments),
+            serde_json::to_vec_pretty(&log),
+        ) {
+            (Ok(raw), Ok(list)) => {
+                let raw_key = corrections::raw_segments_key(recording_id);
+                match storage.put(&raw_key, raw).await {
+                    Ok(()) => {
+                        storage
+                            .put(&corrections::corrections_key(recording_id), list)
+                            .await
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            (Err(e), _) | (_, Err(e)) => Err(StorageError::Backend(e.to_string())),
+        };
+        if let Err(e) = stored {
+            println!("Failed to store the uncorrected transcript: {:?}", e);
+            return Err(Box::new(e));
+        }
+    }
+
+    // Label segments with speakers when a diarization tool is configured; a failure here
+    // leaves the transcript without speakers rather than failing the upload
+    let diarize_path = file_path.clone();
+    match tokio::task::spawn_blocking(move || diarization::diarize(&diarize_path)).await? {
+        Ok(Some(spans)) => {
+            println!("Diarization found {} speaker spans", spans.len());
+            diarization::assign_speakers(&mut segments, &spans);
+        }
+        Ok(None) => {}
+        Err(e) => println!("Diarization failed for {}: {}", file_path, e),
+    }
+
+    // Combine all the transcriptions into a single line (remove all line breaks)
+    let transcription_combined = transcript::plain_text(&segments);
+    println!(
+        "Combined transcription of {} characters for {}",
+        transcription_combined.len(),
+        recording_id
+    );
+
+    // Name the transcript after the recording so it can be traced back to its upload
+    let transcription_filename = format!("{}.txt", recording_id);
+    let key = format!("transcriptions/{}", transcription_filename);
+
+    // Attempt to store the combined transcription
+    if let Err(e) = storage
+        .put(&key, transcription_combined.as_bytes().to_vec())
+        .await
+    {
+        println!("Failed to store transcription: {:?}", e);
+        return Err(Box::new(e));
+    }
+
+    // Keep the timestamped segments next to the transcript for chunking and citations
+    if let Err(e) = transcript::save_segments(storage, recording_id, &segments).await {
+        println!("Failed to store transcript segments: {:?}", e);
+        return Err(Box::new(e));
+    }
+
+    // The turn-by-turn transcript is only written when speakers are known
+    let diarized = transcript::has_speakers(&segments);
+    if diarized {
+        let turns = transcript::speaker_turns(&segments);
+        if let Err(e) = transcript::save_turns(storage, recording_id, &turns).await {
+            println!("Failed to store speaker turns: {:?}", e);
+            return Err(Box::new(e));
+        }
+    }
+
+    // Debug message to confirm the transcription has been saved
+    println!("Transcription successfully stored as: {}", key);
+
+    // Return only the file name, not the full path, and whether speakers were labelled
+    Ok(ProcessedAudio {
+        transcription_filename,
+        diarized,
+        corrections: log.corrections.len(),
+        skipped_rules: log.skipped_rules,
+    })
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
+
+    // Uploads are staged locally for ffmpeg; everything else lives in the storage backend
+    fs::create_dir_all(WORK_DIR).await?;
+    let backend = storage::from_env().map_err(std::io::Error::other)?;
+
+    // `backend rotate-keys` moves all stored data onto the current encryption key and exits
+    if env::args().nth(1).as_deref() == Some("rotate-keys") {
+        let keyring = encryption::Keyring::from_env()
+            .map_err(std::io::Error::other)?
+            .ok_or_else(|| std::io::Error::other("ENCRYPTION_KEY_FILE must be set"))?;
+        let (rotated, unchanged) = encryption::rotate_keys(backend.as_ref(), &keyring)
+            .await
+            .map_err(std::io::Error::other)?;
+        println!(
+            "Key rotation complete: {} objects rotated, {} already current",
+            rotated, unchanged
+        );
+        return Ok(());
+    }
+
+    let storage: web::Data<dyn Storage> =
+        web::Data::from(encryption::wrap_from_env(backend).map_err(std::io::Error::other)?);
+
+    // Open the metadata database shared by all workers
+    let database_path = env::var("DATABASE_PATH").unwrap_or_else(|_| "./hearings.db".to_string());
+    let db = web::Data::new(
+        db::Db::open(&database_path)
+            .map_err(|e| std::io::Error::other(format!("Failed to open database: {}", e)))?,
+    );
+
+    // Finish purging files that earlier deletions staged but could not remove, and put back
+    // files staged by deletions that were interrupted before they were recorded
+    match deletion::resume_purges(&db, storage.get_ref()).await {
+        Ok(0) => {}
+        Ok(purged) => println!("Purged {} files left by earlier deletions", purged),
+        Err(e) => eprintln!("Failed to resume pending purges: {:?}", e),
+    }
+    match deletion::restore_abandoned(&db, storage.get_ref()).await {
+        Ok(0) => {}
+        Ok(restored) => println!(
+            "Restored {} files staged by interrupted deletions",
+            restored
+        ),
+        Err(e) => eprintln!("Failed to restore files of interrupted deletions: {:?}", e),
+    }
+
+    // Prices used to estimate spend in usage reports
+    let prices = web::Data::new(PriceTable::from_env().map_err(std::io::Error::other)?);
+
+    // Prompt templates are re-read from disk whenever they change
+    let prompts = web::Data::new(PromptLibrary::from_env());
+
+    // Start the Actix Web server
+    HttpServer::new(move || {
+        App::new()
+            .app_data(db.clone())
+            .app_data(storage.clone())
+            .app_data(prompts.clone())
+            .app_data(prices.clone())
+            .wrap(
+                // Configure CORS properly
+                Cors::default()
+                    .allow_any_origin()
+                    .allowed_methods(vec![
+                        "GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS",
+                    ])
+                    .allowed_headers(vec![
+                        http::header::AUTHORIZATION,
+                        http::header::ACCEPT,
+                        http::header::CONTENT_TYPE,
+                        http::header::RANGE,
+                        http::header::IF_NONE_MATCH,
+                        http::header::IF_MODIFIED_SINCE,
+                        http::header::IF_RANGE,
+                    ])
+                    // Let the player and downloads read partial content and caching headers
+                    .expose_headers(vec![
+                        http::header::CONTENT_RANGE,
+                        http::header::ACCEPT_RANGES,
+                        http::header::ETAG,
+                        http::header::CONTENT_DISPOSITION,
+                    ])
+                    .supports_credentials()
+                    .max_age(3600),
+            )
+            .service(upload_audio)
+            .service(download_file)
+            .service(recording_audio)
+            .service(recording_waveform)
+            .service(get_transcript)
+            .service(edit_transcript)
+            .service(list_transcript_revisions)
+            .service(transcript_diff)
+            .service(get_vocabulary)
+            .service(put_vocabulary)
+            .service(get_corrections)
+            .service(put_corrections)
+            .service(transcript_corrections)
+            .service(health)
+            .service(summarize)
+            .service(key_points)
+            .service(action_items)
+            .service(participants)
+            .service(list_recordings)
+            .service(get_recording)
+            .service(delete_recording)
+            .service(list_prompts)
+            .service(create_analysis)
+            .service(get_analysis)
+            .service(analyze)
+            .service(ask)
+            .service(usage_report)
+            .service(get_speakers)
+            .service(put_speakers)
+            .service(suggest_speakers)
+            .service(export_transcript)
+            .service(recording_report)
+    })
+    .bind(("0.0.0.0", port.parse().unwrap()))?
+    .run()
+    .await
+}
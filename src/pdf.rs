@@ -0,0 +1,150 @@
+// Minimal PDF writer for text documents. Only the standard Courier fonts are used: they need
+// no embedding, and being monospaced, callers can lay text out by character count.
+
+// US Letter, in points
+pub const PAGE_WIDTH: f64 = 612.0;
+pub const PAGE_HEIGHT: f64 = 792.0;
+
+// Width of one Courier character as a fraction of the font size
+pub const CHAR_WIDTH: f64 = 0.6;
+
+// One run of text placed on a page; `y` is measured from the bottom edge
+pub struct PdfText {
+    pub x: f64,
+    pub y: f64,
+    pub size: f64,
+    pub bold: bool,
+    pub text: String,
+}
+
+#[derive(Default)]
+pub struct PdfPage {
+    pub texts: Vec<PdfText>,
+}
+
+impl PdfPage {
+    pub fn text(&mut self, x: f64, y: f64, size: f64, bold: bool, text: &str) {
+        self.texts.push(PdfText {
+            x,
+            y,
+            size,
+            bold,
+            text: text.to_string(),
+        });
+    }
+}
+
+// Encode text as a PDF string literal in WinAnsiEncoding; characters it cannot represent
+// become `?`
+fn string_literal(text: &str) -> Vec<u8> {
+    let mut out = vec![b'('];
+    for c in text.chars() {
+        let byte = match c {
+            '(' | ')' | '\\' => {
+                out.push(b'\\');
+                c as u8
+            }
+            '\u{20ac}' => 0x80,
+            '\u{2026}' => 0x85,
+            '\u{2018}' => 0x91,
+            '\u{2019}' => 0x92,
+            '\u{201c}' => 0x93,
+            '\u{201d}' => 0x94,
+            '\u{2022}' => 0x95,
+            '\u{2013}' => 0x96,
+            '\u{2014}' => 0x97,
+            c if (' '..='~').contains(&c) || ('\u{a0}'..='\u{ff}').contains(&c) => c as u8,
+            _ => b'?',
+        };
+        out.push(byte);
+    }
+    out.push(b')');
+    out
+}
+
+fn content_stream(page: &PdfPage) -> Vec<u8> {
+    let mut stream = Vec::new();
+    for text in &page.texts {
+        let font = if text.bold { "F2" } else { "F1" };
+        stream.extend_from_slice(
+            format!(
+                "BT /{} {} Tf {:.2} {:.2} Td ",
+                font, text.size, text.x, text.y
+            )
+            .as_bytes(),
+        );
+        stream.extend_from_slice(&string_literal(&text.text));
+        stream.extend_from_slice(b" Tj ET\n");
+    }
+    stream
+}
+
+// Serialise pages into a complete PDF file
+pub fn render(title: &str, pages: &[PdfPage]) -> Vec<u8> {
+    // Fixed objects: 1 catalog, 2 page tree, 3 and 4 fonts, 5 document info; each page then
+    // takes two objects, the page and its content stream
+    let page_ids: Vec<usize> = (0..pages.len()).map(|i| 6 + i * 2).collect();
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            page_ids
+                .iter()
+                .map(|id| format!("{} 0 R", id))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>".to_vec(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier-Bold /Encoding /WinAnsiEncoding >>"
+            .to_vec(),
+        [b"<< /Title ".as_slice(), &string_literal(title), b" >>"].concat(),
+    ];
+    for (page, id) in pages.iter().zip(&page_ids) {
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                id + 1
+            )
+            .into_bytes(),
+        );
+        let stream = content_stream(page);
+        objects.push(
+            [
+                format!("<< /Length {} >>\nstream\n", stream.len()).as_bytes(),
+                &stream,
+                b"endstream",
+            ]
+            .concat(),
+        );
+    }
+
+    let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+        out.extend_from_slice(object);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = out.len();
+    out.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+    out
+}
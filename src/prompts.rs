@@ -0,0 +1,208 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+// Templates used when the prompts directory has no file for an analysis
+const BUILTIN_TEMPLATES: [(&str, &str); 4] = [
+    (
+        "summary",
+        "Summarize the following transcription of the hearing \"{{title}}\" held on {{date}}. \
+         Cover the purpose of the hearing, the main arguments and any decisions or outcomes. \
+         Known participants: {{participants}}. Write the summary in {{language}}.",
+    ),
+    (
+        "key_points",
+        "Extract the key points from the following transcription of the hearing \"{{title}}\" \
+         held on {{date}}. Known participants: {{participants}}. Write each point in {{language}}.",
+    ),
+    (
+        "action_items",
+        "Extract the action items from the following transcription of the hearing \"{{title}}\" \
+         held on {{date}}: tasks, orders, filings or follow-ups that someone has to carry out. \
+         Known participants: {{participants}}. Write each item in {{language}}.",
+    ),
+    (
+        "participants",
+        "Extract the participants and their details from the following transcription of the \
+         hearing \"{{title}}\" held on {{date}}. Use {{language}} for roles and descriptions.",
+    ),
+];
+
+// Version reported for built-in templates; bump when their text changes
+const BUILTIN_VERSION: &str = "builtin-1";
+
+// Values substituted for `{{name}}` placeholders
+pub struct PromptVariables {
+    pub title: String,
+    pub date: String,
+    pub participants: String,
+    pub language: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub version: String,
+    pub source: String,
+    #[serde(skip)]
+    pub body: String,
+    #[serde(skip)]
+    modified: Option<SystemTime>,
+}
+
+impl PromptTemplate {
+    // A one-off template from instructions supplied with a request
+    pub fn inline(instructions: &str) -> PromptTemplate {
+        let digest = hex::encode(Sha256::digest(instructions.as_bytes()));
+        PromptTemplate {
+            name: "instructions".to_string(),
+            version: format!("inline-sha256-{}", &digest[..12]),
+            source: "request".to_string(),
+            body: instructions.trim().to_string(),
+            modified: None,
+        }
+    }
+
+    pub fn is_inline(&self) -> bool {
+        self.source == "request"
+    }
+
+    pub fn render(&self, variables: &PromptVariables) -> String {
+        self.body
+            .replace("{{title}}", &variables.title)
+            .replace("{{date}}", &variables.date)
+            .replace("{{participants}}", &variables.participants)
+            .replace("{{language}}", &variables.language)
+    }
+}
+
+// Parse a template file. An optional header of `key: value` lines ended by `---` may set
+// `version`; without it the version is derived from the file contents.
+fn parse_template(name: &str, contents: &str, source: String) -> PromptTemplate {
+    let mut version = None;
+    let mut body = contents;
+    if let Some((header, rest)) = contents.split_once("\n---\n") {
+        let fields: Vec<(&str, &str)> = header
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .collect();
+        if !fields.is_empty() && fields.len() == header.lines().count() {
+            version = fields
+                .iter()
+                .find(|(k, _)| *k == "version")
+                .map(|(_, v)| v.to_string());
+            body = rest;
+        }
+    }
+    let version = version.unwrap_or_else(|| {
+        let digest = hex::encode(Sha256::digest(contents.as_bytes()));
+        format!("sha256-{}", &digest[..12])
+    });
+    PromptTemplate {
+        name: name.to_string(),
+        version,
+        source,
+        body: body.trim().to_string(),
+        modified: None,
+    }
+}
+
+// Loads `<name>.txt` templates from the prompts directory, re-reading a file whenever its
+// modification time changes so edits take effect without a restart
+pub struct PromptLibrary {
+    directory: PathBuf,
+    cache: RwLock<HashMap<String, PromptTemplate>>,
+}
+
+impl PromptLibrary {
+    pub fn from_env() -> PromptLibrary {
+        let directory = env::var("PROMPTS_DIR").unwrap_or_else(|_| "./prompts".to_string());
+        PromptLibrary {
+            directory: PathBuf::from(directory),
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    // Template names are used as file names, so keep them to a safe character set
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    }
+
+    fn builtin(name: &str) -> Option<PromptTemplate> {
+        BUILTIN_TEMPLATES
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map(|(_, body)| PromptTemplate {
+                name: name.to_string(),
+                version: BUILTIN_VERSION.to_string(),
+                source: "builtin".to_string(),
+                body: body.to_string(),
+                modified: None,
+            })
+    }
+
+    pub fn get(&self, name: &str) -> Option<PromptTemplate> {
+        if !Self::is_valid_name(name) {
+            return None;
+        }
+        let path = self.directory.join(format!("{}.txt", name));
+        let modified = match std::fs::metadata(&path).and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(_) => {
+                self.cache.write().unwrap().remove(name);
+                return Self::builtin(name);
+            }
+        };
+
+        if let Some(cached) = self.cache.read().unwrap().get(name) {
+            if cached.modified == Some(modified) {
+                return Some(cached.clone());
+            }
+        }
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                let mut template = parse_template(name, &contents, path.display().to_string());
+                template.modified = Some(modified);
+                println!("Loaded prompt template {} ({})", name, template.version);
+                self.cache
+                    .write()
+                    .unwrap()
+                    .insert(name.to_string(), template.clone());
+                Some(template)
+            }
+            Err(e) => {
+                println!("Failed to read prompt template {}: {:?}", path.display(), e);
+                Self::builtin(name)
+            }
+        }
+    }
+
+    // Every template available, from the directory and the built-in set
+    pub fn list(&self) -> Vec<PromptTemplate> {
+        let mut names: Vec<String> = BUILTIN_TEMPLATES
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        if let Ok(entries) = std::fs::read_dir(&self.directory) {
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                if let Some(name) = file_name.strip_suffix(".txt") {
+                    if !names.iter().any(|n| n == name) {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+        names.sort();
+        names.iter().filter_map(|name| self.get(name)).collect()
+    }
+}
@@ -0,0 +1,208 @@
+use crate::analysis::OutputFormat;
+use crate::transcript::{format_timestamp, TranscriptSegment};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+
+// How many matching segments are handed to the model by default, and at most
+pub const DEFAULT_TOP_K: usize = 6;
+pub const MAX_TOP_K: usize = 20;
+
+// Segments on either side of a match included for context
+const CONTEXT_SEGMENTS: usize = 1;
+
+// BM25 parameters
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+const STOPWORDS: [&str; 48] = [
+    "a", "about", "an", "and", "are", "as", "at", "be", "but", "by", "did", "do", "does", "for",
+    "from", "had", "has", "have", "he", "her", "his", "how", "i", "in", "is", "it", "its", "of",
+    "on", "or", "say", "said", "she", "that", "the", "their", "they", "this", "to", "was", "we",
+    "were", "what", "when", "where", "which", "who", "why",
+];
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.len() > 1 && !STOPWORDS.contains(word))
+        .map(str::to_string)
+        .collect()
+}
+
+// Rank segments against the question with BM25, scoring each segment together with its
+// neighbours so a match split across segment boundaries still counts. Returns segment
+// indices in chronological order: the best matches plus their context.
+pub fn retrieve(segments: &[TranscriptSegment], question: &str, top_k: usize) -> Vec<usize> {
+    let query: HashSet<String> = tokenize(question).into_iter().collect();
+    if query.is_empty() || segments.is_empty() {
+        return Vec::new();
+    }
+
+    let windows: Vec<Vec<String>> = (0..segments.len())
+        .map(|i| {
+            let start = i.saturating_sub(CONTEXT_SEGMENTS);
+            let end = (i + CONTEXT_SEGMENTS + 1).min(segments.len());
+            segments[start..end]
+                .iter()
+                .flat_map(|segment| tokenize(&segment.text))
+                .collect()
+        })
+        .collect();
+
+    let average_length = windows.iter().map(Vec::len).sum::<usize>() as f64 / windows.len() as f64;
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for window in &windows {
+        let unique: HashSet<&str> = window.iter().map(String::as_str).collect();
+        for term in unique {
+            if query.contains(term) {
+                *document_frequency.entry(term).or_default() += 1;
+            }
+        }
+    }
+
+    let total = windows.len() as f64;
+    let mut scored: Vec<(usize, f64)> = windows
+        .iter()
+        .enumerate()
+        .map(|(i, window)| {
+            let length = window.len() as f64;
+            let score = query
+                .iter()
+                .map(|term| {
+                    let frequency = window.iter().filter(|t| *t == term).count() as f64;
+                    if frequency == 0.0 {
+                        return 0.0;
+                    }
+                    let df = document_frequency[term.as_str()] as f64;
+                    let idf = ((total - df + 0.5) / (df + 0.5) + 1.0).ln();
+                    idf * frequency * (K1 + 1.0)
+                        / (frequency + K1 * (1.0 - B + B * length / average_length.max(1.0)))
+                })
+                .sum::<f64>();
+            (i, score)
+        })
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut selected: Vec<usize> = scored
+        .into_iter()
+        .take(top_k)
+        .flat_map(|(i, _)| {
+            i.saturating_sub(CONTEXT_SEGMENTS)..(i + CONTEXT_SEGMENTS + 1).min(segments.len())
+        })
+        .collect();
+    selected.sort_unstable();
+    selected.dedup();
+    selected
+}
+
+// The retrieved segments as the model sees them, each tagged with its index for citing
+pub fn render_excerpts(segments: &[TranscriptSegment], indices: &[usize]) -> String {
+    let mut lines = Vec::with_capacity(indices.len());
+    let mut previous: Option<usize> = None;
+    for &index in indices {
+        if previous.is_some_and(|p| p + 1 != index) {
+            lines.push("...".to_string());
+        }
+        let segment = &segments[index];
+        lines.push(format!(
+            "[S{} {}] {}",
+            index,
+            format_timestamp(segment.start),
+            segment.attributed_text()
+        ));
+        previous = Some(index);
+    }
+    lines.join("\n")
+}
+
+pub fn instructions(question: &str) -> String {
+    format!(
+        "You answer questions about a hearing using only the transcript excerpts provided. \
+         Each excerpt line starts with a segment tag such as [S12 00:04:31]. Answer the question \
+         below in a few sentences and cite the segment numbers your answer relies on. If the \
+         excerpts do not contain the answer, say so and cite nothing.\n\nQuestion: {}",
+        question
+    )
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Answer {
+    pub answer: String,
+    pub citations: Vec<usize>,
+}
+
+fn answer_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "answer": {
+                "type": "string",
+                "description": "Answer to the question, based only on the excerpts"
+            },
+            "citations": {
+                "type": "array",
+                "description": "Numbers of the segments the answer relies on, e.g. 12 for [S12 ...]",
+                "items": { "type": "integer" }
+            }
+        },
+        "required": ["answer", "citations"],
+        "additionalProperties": false
+    })
+}
+
+fn validate_answer(output: &str) -> Result<(), String> {
+    serde_json::from_str::<Answer>(output)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+pub const ANSWER_FORMAT: OutputFormat = OutputFormat {
+    name: "answer",
+    schema: answer_schema,
+    validate: validate_answer,
+};
+
+// A cited segment, with the times a player needs to jump to it
+#[derive(Serialize, Clone, Debug)]
+pub struct Citation {
+    pub segment: usize,
+    pub start: f64,
+    pub end: f64,
+    pub timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    pub text: String,
+}
+
+// Resolve cited segment numbers, ignoring any that were not among the excerpts
+pub fn citations(
+    segments: &[TranscriptSegment],
+    retrieved: &[usize],
+    cited: &[usize],
+) -> Vec<Citation> {
+    let mut cited: Vec<usize> = cited
+        .iter()
+        .copied()
+        .filter(|index| retrieved.contains(index))
+        .collect();
+    cited.sort_unstable();
+    cited.dedup();
+    cited
+        .into_iter()
+        .map(|index| {
+            let segment = &segments[index];
+            Citation {
+                segment: index,
+                start: segment.start,
+                end: segment.end,
+                timestamp: format_timestamp(segment.start),
+                speaker: segment.speaker.clone(),
+                text: segment.text.trim().to_string(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,102 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Artifact {
+    pub kind: String,
+    pub file: String,
+    pub model: Option<String>,
+    // Version of the prompt template an analysis was generated with
+    pub prompt_version: Option<String>,
+    // Identifies the inputs an analysis was generated from, so identical requests can reuse it
+    pub cache_key: Option<String>,
+    pub created_at: DateTime<Utc>,
+    // Transcript revision an analysis was generated from
+    #[serde(default)]
+    pub transcript_revision: Option<u32>,
+    // Set on analyses when the transcript has been edited since they were generated
+    #[serde(default)]
+    pub stale: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Recording {
+    pub id: String,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub duration_secs: Option<usize>,
+    pub upload: Option<Artifact>,
+    pub transcript: Option<Artifact>,
+    // Turn-by-turn transcript, present when diarization labelled the speakers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turns: Option<Artifact>,
+    // Transcript segments before the correction dictionary was applied, and the corrections
+    // made; present when the dictionary changed something
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_transcript: Option<Artifact>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corrections: Option<Artifact>,
+    // Low-bitrate copy of the upload for playback in the browser
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rendition: Option<Artifact>,
+    // Peak data for drawing the recording's waveform
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub waveform: Option<Artifact>,
+    pub analyses: Vec<Artifact>,
+}
+
+impl Recording {
+    pub fn new(id: &str) -> Self {
+        Recording {
+            id: id.to_string(),
+            title: None,
+            tags: Vec::new(),
+            status: "processing".to_string(),
+            created_at: Utc::now(),
+            duration_secs: None,
+            upload: None,
+            transcript: None,
+            turns: None,
+            raw_transcript: None,
+            corrections: None,
+            rendition: None,
+            waveform: None,
+            analyses: Vec::new(),
+        }
+    }
+}
+
+impl Artifact {
+    pub fn new(kind: &str, file: &str, model: Option<&str>) -> Self {
+        Artifact {
+            kind: kind.to_string(),
+            file: file.to_string(),
+            model: model.map(|m| m.to_string()),
+            prompt_version: None,
+            cache_key: None,
+            created_at: Utc::now(),
+            transcript_revision: None,
+            stale: false,
+        }
+    }
+}
+
+// Recording IDs are the upload UUID; older clients pass the transcript file name instead
+pub fn recording_id_from_filename(uuid_filename: &str) -> &str {
+    uuid_filename.trim_end_matches(".txt")
+}
+
+// Only accept IDs that are plain UUIDs so they can be used safely in file paths
+pub fn is_valid_id(id: &str) -> bool {
+    uuid::Uuid::parse_str(id).is_ok()
+}
+
+// Split a comma separated tag list, dropping empty entries
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
@@ -0,0 +1,362 @@
+use crate::pdf::{self, PdfPage};
+use std::io::Write;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+// A hearing report: recording details followed by one section per analysis
+pub struct Report {
+    pub title: String,
+    pub details: Vec<(&'static str, String)>,
+    pub sections: Vec<Section>,
+}
+
+pub struct Section {
+    pub heading: &'static str,
+    pub content: SectionContent,
+    // Generated before the latest transcript edit, so it may not match the transcript
+    pub stale: bool,
+}
+
+pub enum SectionContent {
+    Paragraphs(Vec<String>),
+    Items(Vec<String>),
+    // The analysis has not been run for this recording
+    Missing,
+}
+
+const MISSING_TEXT: &str = "Not generated yet.";
+const STALE_TEXT: &str =
+    "Generated from an earlier transcript revision; it may not reflect later edits.";
+
+// Split stored text into paragraphs at blank lines
+pub fn paragraphs(text: &str) -> Vec<String> {
+    text.split("\n\n")
+        .map(|p| p.lines().map(str::trim).collect::<Vec<_>>().join(" "))
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+// Items from a stored `.txt` list, one per `- ` line
+pub fn list_items(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.trim().trim_start_matches("- ").to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+pub fn render_markdown(report: &Report) -> String {
+    let mut out = format!("# {}\n\n", report.title);
+    for (label, value) in &report.details {
+        out.push_str(&format!("- **{}:** {}\n", label, value));
+    }
+    for section in &report.sections {
+        out.push_str(&format!("\n## {}\n\n", section.heading));
+        if section.stale {
+            out.push_str(&format!("> {}\n\n", STALE_TEXT));
+        }
+        match &section.content {
+            SectionContent::Paragraphs(paragraphs) => {
+                out.push_str(&paragraphs.join("\n\n"));
+                out.push('\n');
+            }
+            SectionContent::Items(items) => {
+                for item in items {
+                    out.push_str(&format!("- {}\n", item));
+                }
+            }
+            SectionContent::Missing => out.push_str(&format!("_{}_\n", MISSING_TEXT)),
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+pub fn render_html(report: &Report) -> String {
+    let title = escape_html(&report.title);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>body {{ font-family: sans-serif; max-width: 50em; margin: 2em auto; \
+         line-height: 1.5; }} dt {{ font-weight: bold; }} .missing {{ color: #777; }} \
+         .stale {{ color: #a15c00; font-style: italic; }}</style>\n\
+         </head>\n<body>\n<h1>{}</h1>\n<dl>\n",
+        title, title
+    );
+    for (label, value) in &report.details {
+        out.push_str(&format!(
+            "<dt>{}</dt><dd>{}</dd>\n",
+            escape_html(label),
+            escape_html(value)
+        ));
+    }
+    out.push_str("</dl>\n");
+    for section in &report.sections {
+        out.push_str(&format!("<h2>{}</h2>\n", escape_html(section.heading)));
+        if section.stale {
+            out.push_str(&format!("<p class=\"stale\">{}</p>\n", STALE_TEXT));
+        }
+        match &section.content {
+            SectionContent::Paragraphs(paragraphs) => {
+                for paragraph in paragraphs {
+                    out.push_str(&format!("<p>{}</p>\n", escape_html(paragraph)));
+                }
+            }
+            SectionContent::Items(items) => {
+                out.push_str("<ul>\n");
+                for item in items {
+                    out.push_str(&format!("<li>{}</li>\n", escape_html(item)));
+                }
+                out.push_str("</ul>\n");
+            }
+            SectionContent::Missing => {
+                out.push_str(&format!("<p class=\"missing\">{}</p>\n", MISSING_TEXT));
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+// Break text into lines of at most `width` characters
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word = word;
+        while word.chars().count() > width {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            let split = word.char_indices().nth(width).map(|(i, _)| i).unwrap();
+            lines.push(word[..split].to_string());
+            word = &word[split..];
+        }
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+// Lays out text top to bottom, starting a new page when the current one is full
+struct PdfLayout {
+    pages: Vec<PdfPage>,
+    y: f64,
+}
+
+impl PdfLayout {
+    const MARGIN: f64 = 54.0;
+
+    fn new() -> PdfLayout {
+        PdfLayout {
+            pages: vec![PdfPage::default()],
+            y: pdf::PAGE_HEIGHT - Self::MARGIN,
+        }
+    }
+
+    fn space(&mut self, points: f64) {
+        self.y -= points;
+    }
+
+    // Wrapped text at `indent` points from the margin; `first_prefix` starts the first line
+    fn text(&mut self, text: &str, size: f64, bold: bool, indent: f64, first_prefix: &str) {
+        let leading = size * 1.4;
+        let usable = pdf::PAGE_WIDTH - 2.0 * Self::MARGIN - indent;
+        let width = (usable / (pdf::CHAR_WIDTH * size)) as usize;
+        let prefix_width = first_prefix.chars().count();
+        for (index, line) in wrap(text, width.saturating_sub(prefix_width).max(1))
+            .into_iter()
+            .enumerate()
+        {
+            if self.y - leading < Self::MARGIN + 20.0 {
+                self.pages.push(PdfPage::default());
+                self.y = pdf::PAGE_HEIGHT - Self::MARGIN;
+            }
+            self.y -= leading;
+            let prefix = if index == 0 {
+                first_prefix.to_string()
+            } else {
+                " ".repeat(prefix_width)
+            };
+            let x = Self::MARGIN + indent;
+            let page = self.pages.last_mut().unwrap();
+            page.text(x, self.y, size, bold, &format!("{}{}", prefix, line));
+        }
+    }
+}
+
+pub fn render_pdf(report: &Report) -> Vec<u8> {
+    const BODY: f64 = 10.0;
+    let mut layout = PdfLayout::new();
+    layout.text(&report.title, 16.0, true, 0.0, "");
+    layout.space(8.0);
+    for (label, value) in &report.details {
+        layout.text(
+            value,
+            BODY,
+            false,
+            0.0,
+            &format!("{:<14}", format!("{}:", label)),
+        );
+    }
+    for section in &report.sections {
+        layout.space(14.0);
+        layout.text(section.heading, 13.0, true, 0.0, "");
+        layout.space(4.0);
+        if section.stale {
+            layout.text(STALE_TEXT, BODY, false, 0.0, "");
+            layout.space(6.0);
+        }
+        match &section.content {
+            SectionContent::Paragraphs(paragraphs) => {
+                for paragraph in paragraphs {
+                    layout.text(paragraph, BODY, false, 0.0, "");
+                    layout.space(6.0);
+                }
+            }
+            SectionContent::Items(items) => {
+                for item in items {
+                    layout.text(item, BODY, false, 12.0, "\u{2022} ");
+                    layout.space(2.0);
+                }
+            }
+            SectionContent::Missing => layout.text(MISSING_TEXT, BODY, false, 0.0, ""),
+        }
+    }
+
+    // Page numbers go in once the page count is known
+    let total = layout.pages.len();
+    for (index, page) in layout.pages.iter_mut().enumerate() {
+        let label = format!("Page {} of {}", index + 1, total);
+        let x = (pdf::PAGE_WIDTH - label.len() as f64 * pdf::CHAR_WIDTH * 9.0) / 2.0;
+        page.text(x, 36.0, 9.0, false, &label);
+    }
+    pdf::render(&report.title, &layout.pages)
+}
+
+fn escape_xml(text: &str) -> String {
+    // Control characters other than tab and newline are not allowed in XML 1.0
+    escape_html(text)
+        .chars()
+        .filter(|c| !c.is_control() || *c == '\t' || *c == '\n')
+        .collect()
+}
+
+fn docx_paragraph(style: Option<&str>, text: &str, bold: bool) -> String {
+    let properties = style
+        .map(|style| format!("<w:pPr><w:pStyle w:val=\"{}\"/></w:pPr>", style))
+        .unwrap_or_default();
+    let run_properties = if bold { "<w:rPr><w:b/></w:rPr>" } else { "" };
+    format!(
+        "<w:p>{}<w:r>{}<w:t xml:space=\"preserve\">{}</w:t></w:r></w:p>",
+        properties,
+        run_properties,
+        escape_xml(text)
+    )
+}
+
+const DOCX_CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+<Default Extension="xml" ContentType="application/xml"/>
+<Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/>
+<Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/>
+<Override PartName="/docProps/core.xml" ContentType="application/vnd.openxmlformats-package.core-properties+xml"/>
+</Types>"#;
+
+const DOCX_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>
+<Relationship Id="rId2" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/>
+</Relationships>"#;
+
+const DOCX_DOCUMENT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>
+</Relationships>"#;
+
+// Word's built-in style names, so the headings show up in its navigation pane
+const DOCX_STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+<w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/><w:pPr><w:spacing w:after="120"/></w:pPr><w:rPr><w:sz w:val="22"/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="Title"><w:name w:val="Title"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:pPr><w:spacing w:after="240"/></w:pPr><w:rPr><w:b/><w:sz w:val="40"/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="Heading1"><w:name w:val="heading 1"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:pPr><w:keepNext/><w:spacing w:before="360" w:after="120"/><w:outlineLvl w:val="0"/></w:pPr><w:rPr><w:b/><w:sz w:val="30"/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="ListItem"><w:name w:val="List Item"/><w:basedOn w:val="Normal"/><w:pPr><w:spacing w:after="60"/><w:ind w:left="567" w:hanging="283"/></w:pPr></w:style>
+</w:styles>"#;
+
+pub fn render_docx(report: &Report) -> Result<Vec<u8>, zip::result::ZipError> {
+    let mut body = docx_paragraph(Some("Title"), &report.title, false);
+    for (label, value) in &report.details {
+        body.push_str(&format!(
+            "<w:p><w:r><w:rPr><w:b/></w:rPr><w:t xml:space=\"preserve\">{}: </w:t></w:r>\
+             <w:r><w:t xml:space=\"preserve\">{}</w:t></w:r></w:p>",
+            escape_xml(label),
+            escape_xml(value)
+        ));
+    }
+    for section in &report.sections {
+        body.push_str(&docx_paragraph(Some("Heading1"), section.heading, false));
+        if section.stale {
+            body.push_str(&docx_paragraph(None, STALE_TEXT, true));
+        }
+        match &section.content {
+            SectionContent::Paragraphs(paragraphs) => {
+                for paragraph in paragraphs {
+                    body.push_str(&docx_paragraph(None, paragraph, false));
+                }
+            }
+            SectionContent::Items(items) => {
+                for item in items {
+                    body.push_str(&docx_paragraph(
+                        Some("ListItem"),
+                        &format!("\u{2022}\t{}", item),
+                        false,
+                    ));
+                }
+            }
+            SectionContent::Missing => body.push_str(&docx_paragraph(None, MISSING_TEXT, false)),
+        }
+    }
+    let document = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <w:document xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\">\
+         <w:body>{}<w:sectPr><w:pgSz w:w=\"12240\" w:h=\"15840\"/>\
+         <w:pgMar w:top=\"1440\" w:right=\"1440\" w:bottom=\"1440\" w:left=\"1440\" \
+         w:header=\"720\" w:footer=\"720\" w:gutter=\"0\"/></w:sectPr></w:body></w:document>",
+        body
+    );
+    let core = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <cp:coreProperties xmlns:cp=\"http://schemas.openxmlformats.org/package/2006/metadata/core-properties\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\"><dc:title>{}</dc:title></cp:coreProperties>",
+        escape_xml(&report.title)
+    );
+
+    let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    for (name, contents) in [
+        ("[Content_Types].xml", DOCX_CONTENT_TYPES),
+        ("_rels/.rels", DOCX_RELS),
+        ("word/_rels/document.xml.rels", DOCX_DOCUMENT_RELS),
+        ("word/styles.xml", DOCX_STYLES),
+        ("word/document.xml", document.as_str()),
+        ("docProps/core.xml", core.as_str()),
+    ] {
+        zip.start_file(name, options)?;
+        zip.write_all(contents.as_bytes())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
@@ -0,0 +1,255 @@
+use crate::transcript::TranscriptSegment;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Author recorded for the transcript as it came from speech-to-text
+pub const ORIGINAL_AUTHOR: &str = "transcription";
+
+// Longest segment text and author name accepted in an edit
+pub const MAX_SEGMENT_CHARS: usize = 10_000;
+pub const MAX_AUTHOR_CHARS: usize = 200;
+
+// One saved state of a transcript. Revision 1 is the transcript as transcribed; every edit
+// adds the next number.
+#[derive(Serialize, Clone, Debug)]
+pub struct Revision {
+    pub revision: u32,
+    pub author: String,
+    pub comment: Option<String>,
+    pub changed_segments: usize,
+    pub file: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// Storage key of the segments as they were at a revision
+pub fn revision_key(recording_id: &str, revision: u32) -> String {
+    format!(
+        "transcriptions/{}.r{}.segments.json",
+        recording_id, revision
+    )
+}
+
+// A change to one segment, addressed by its position in the transcript
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SegmentEdit {
+    pub index: usize,
+    pub text: Option<String>,
+    // Move the segment to another diarization label already used in the transcript
+    pub speaker: Option<String>,
+}
+
+// Apply edits in place, returning how many segments actually changed. Nothing is changed
+// when an edit is invalid.
+pub fn apply_edits(
+    segments: &mut [TranscriptSegment],
+    edits: &[SegmentEdit],
+    labels: &[String],
+) -> Result<usize, String> {
+    let mut seen = Vec::new();
+    for edit in edits {
+        if edit.index >= segments.len() {
+            return Err(format!(
+                "Segment {} does not exist; the transcript has {} segments",
+                edit.index,
+                segments.len()
+            ));
+        }
+        if seen.contains(&edit.index) {
+            return Err(format!("Segment {} is edited twice", edit.index));
+        }
+        seen.push(edit.index);
+        if edit.text.is_none() && edit.speaker.is_none() {
+            return Err(format!("Edit of segment {} changes nothing", edit.index));
+        }
+        if let Some(text) = &edit.text {
+            if text.trim().is_empty() || text.chars().count() > MAX_SEGMENT_CHARS {
+                return Err(format!(
+                    "Text of segment {} must be between 1 and {} characters",
+                    edit.index, MAX_SEGMENT_CHARS
+                ));
+            }
+        }
+        if let Some(speaker) = &edit.speaker {
+            if !labels.contains(speaker) {
+                return Err(format!("Unknown speaker label: {}", speaker));
+            }
+        }
+    }
+
+    let mut changed = 0;
+    for edit in edits {
+        let segment = &mut segments[edit.index];
+        let before = (segment.text.clone(), segment.speaker.clone());
+        if let Some(text) = &edit.text {
+            segment.text = text.trim().to_string();
+        }
+        if let Some(speaker) = &edit.speaker {
+            segment.speaker = Some(speaker.clone());
+        }
+        if before != (segment.text.clone(), segment.speaker.clone()) {
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SegmentState {
+    pub text: String,
+    pub speaker: Option<String>,
+}
+
+// A segment that differs between two revisions
+#[derive(Serialize, Clone, Debug)]
+pub struct SegmentChange {
+    pub index: usize,
+    pub start: f64,
+    pub end: f64,
+    pub before: Option<SegmentState>,
+    pub after: Option<SegmentState>,
+}
+
+fn state(segment: Option<&TranscriptSegment>) -> Option<SegmentState> {
+    segment.map(|segment| SegmentState {
+        text: segment.text.trim().to_string(),
+        speaker: segment.speaker.clone(),
+    })
+}
+
+// Segment-by-segment differences. Edits never add or remove segments, so segments are
+// compared by position.
+pub fn diff(from: &[TranscriptSegment], to: &[TranscriptSegment]) -> Vec<SegmentChange> {
+    (0..from.len().max(to.len()))
+        .filter_map(|index| {
+            let (before, after) = (from.get(index), to.get(index));
+            let unchanged = match (before, after) {
+                (Some(before), Some(after)) => {
+                    before.text.trim() == after.text.trim() && before.speaker == after.speaker
+                }
+                _ => false,
+            };
+            if unchanged {
+                return None;
+            }
+            let timing = after.or(before)?;
+            Some(SegmentChange {
+                index,
+                start: timing.start,
+                end: timing.end,
+                before: state(before),
+                after: state(after),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: f64, text: &str, speaker: Option<&str>) -> TranscriptSegment {
+        TranscriptSegment {
+            start,
+            end: start + 5.0,
+            text: text.to_string(),
+            speaker: speaker.map(str::to_string),
+        }
+    }
+
+    fn transcript() -> Vec<TranscriptSegment> {
+        vec![
+            segment(0.0, " All rise.", Some("Speaker 1")),
+            segment(5.0, " Be seated.", Some("Speaker 2")),
+            segment(10.0, " Call the case.", Some("Speaker 2")),
+        ]
+    }
+
+    #[test]
+    fn identical_revisions_have_no_changes() {
+        assert!(diff(&transcript(), &transcript()).is_empty());
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_not_a_change() {
+        let mut edited = transcript();
+        edited[0].text = "All rise. ".to_string();
+        assert!(diff(&transcript(), &edited).is_empty());
+    }
+
+    #[test]
+    fn reports_text_and_speaker_changes_by_position() {
+        let mut edited = transcript();
+        edited[1].text = "Please be seated.".to_string();
+        edited[2].speaker = Some("Speaker 1".to_string());
+
+        let changes = diff(&transcript(), &edited);
+        assert_eq!(changes.len(), 2);
+
+        assert_eq!((changes[0].index, changes[0].start), (1, 5.0));
+        let before = changes[0].before.as_ref().unwrap();
+        let after = changes[0].after.as_ref().unwrap();
+        assert_eq!(before.text, "Be seated.");
+        assert_eq!(after.text, "Please be seated.");
+        assert_eq!(after.speaker.as_deref(), Some("Speaker 2"));
+
+        assert_eq!(changes[1].index, 2);
+        let before = changes[1].before.as_ref().unwrap();
+        let after = changes[1].after.as_ref().unwrap();
+        assert_eq!(before.text, after.text);
+        assert_eq!(before.speaker.as_deref(), Some("Speaker 2"));
+        assert_eq!(after.speaker.as_deref(), Some("Speaker 1"));
+    }
+
+    #[test]
+    fn segments_on_one_side_only_are_added_or_removed() {
+        let shorter = transcript()[..2].to_vec();
+        let removed = diff(&transcript(), &shorter);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].index, 2);
+        assert_eq!(removed[0].start, 10.0);
+        assert!(removed[0].after.is_none());
+
+        let added = diff(&shorter, &transcript());
+        assert_eq!(added.len(), 1);
+        assert!(added[0].before.is_none());
+        assert_eq!(added[0].after.as_ref().unwrap().text, "Call the case.");
+    }
+
+    #[test]
+    fn edits_apply_together_or_not_at_all() {
+        let labels = vec!["Speaker 1".to_string(), "Speaker 2".to_string()];
+        let mut segments = transcript();
+        let edit = |index, text: Option<&str>, speaker: Option<&str>| SegmentEdit {
+            index,
+            text: text.map(str::to_string),
+            speaker: speaker.map(str::to_string),
+        };
+
+        let invalid = [
+            edit(0, Some("All rise!"), None),
+            edit(1, None, Some("Speaker 9")),
+        ];
+        assert!(apply_edits(&mut segments, &invalid, &labels).is_err());
+        assert!(diff(&transcript(), &segments).is_empty());
+
+        let valid = [
+            edit(0, Some(" All rise! "), None),
+            edit(1, None, Some("Speaker 2")),
+        ];
+        // Assigning a segment its current speaker changes nothing
+        assert_eq!(apply_edits(&mut segments, &valid, &labels), Ok(1));
+        assert_eq!(segments[0].text, "All rise!");
+        assert_eq!(
+            apply_edits(&mut segments, &[edit(0, None, None)], &labels),
+            Err("Edit of segment 0 changes nothing".to_string())
+        );
+        assert!(apply_edits(&mut segments, &[edit(3, Some("x"), None)], &labels).is_err());
+        assert!(apply_edits(
+            &mut segments,
+            &[edit(0, Some("x"), None), edit(0, Some("y"), None)],
+            &labels
+        )
+        .is_err());
+    }
+}
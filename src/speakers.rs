@@ -0,0 +1,114 @@
+use crate::transcript::{format_timestamp, TranscriptSegment};
+use serde::{Deserialize, Serialize};
+
+// Longest name or role accepted for a speaker
+pub const MAX_NAME_CHARS: usize = 200;
+
+// Turns per speaker shown to the model when suggesting names
+const SAMPLE_TURNS: usize = 4;
+
+// The participant a reviewer has assigned to a diarization label
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Speaker {
+    pub label: String,
+    pub name: String,
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+impl Speaker {
+    // How the speaker appears in transcripts and analyses, e.g. "Jane Smith (Judge)"
+    pub fn display_name(&self) -> String {
+        match &self.role {
+            Some(role) => format!("{} ({})", self.name, role),
+            None => self.name.clone(),
+        }
+    }
+}
+
+// A label found in the transcript, with its assigned name when there is one
+#[derive(Serialize, Clone, Debug)]
+pub struct SpeakerLabel {
+    pub label: String,
+    pub name: Option<String>,
+    pub role: Option<String>,
+    pub segments: usize,
+    pub first_spoken_at: String,
+}
+
+// Distinct speaker labels in order of first appearance
+pub fn labels(segments: &[TranscriptSegment]) -> Vec<String> {
+    let mut labels: Vec<String> = Vec::new();
+    for speaker in segments.iter().filter_map(|s| s.speaker.as_ref()) {
+        if !labels.contains(speaker) {
+            labels.push(speaker.clone());
+        }
+    }
+    labels
+}
+
+pub fn describe(segments: &[TranscriptSegment], speakers: &[Speaker]) -> Vec<SpeakerLabel> {
+    labels(segments)
+        .into_iter()
+        .map(|label| {
+            let spoken: Vec<&TranscriptSegment> = segments
+                .iter()
+                .filter(|s| s.speaker.as_deref() == Some(label.as_str()))
+                .collect();
+            let assigned = speakers.iter().find(|speaker| speaker.label == label);
+            SpeakerLabel {
+                name: assigned.map(|speaker| speaker.name.clone()),
+                role: assigned.and_then(|speaker| speaker.role.clone()),
+                segments: spoken.len(),
+                first_spoken_at: format_timestamp(spoken[0].start),
+                label,
+            }
+        })
+        .collect()
+}
+
+// Replace diarization labels with the assigned names; unassigned labels are kept
+pub fn apply_names(segments: &mut [TranscriptSegment], speakers: &[Speaker]) {
+    if speakers.is_empty() {
+        return;
+    }
+    for segment in segments.iter_mut() {
+        let assigned = segment
+            .speaker
+            .as_deref()
+            .and_then(|label| speakers.iter().find(|speaker| speaker.label == label));
+        if let Some(speaker) = assigned {
+            segment.speaker = Some(speaker.display_name());
+        }
+    }
+}
+
+// The first few lines each speaker says, enough for the model to tell who is who
+pub fn sample_text(segments: &[TranscriptSegment]) -> String {
+    let mut lines = Vec::new();
+    for label in labels(segments) {
+        lines.push(format!("## {}", label));
+        lines.extend(
+            segments
+                .iter()
+                .filter(|s| {
+                    s.speaker.as_deref() == Some(label.as_str()) && !s.text.trim().is_empty()
+                })
+                .take(SAMPLE_TURNS)
+                .map(|s| format!("[{}] {}", format_timestamp(s.start), s.text.trim())),
+        );
+        lines.push(String::new());
+    }
+    lines.join("\n")
+}
+
+pub fn suggestion_instructions(participants: &str) -> String {
+    format!(
+        "The transcript excerpts below are grouped by the speaker label a diarization tool \
+         assigned. Match each label to one of the hearing participants listed here, using how \
+         they are addressed and what they say. Give the participant's name and role; leave the \
+         name empty when the excerpts do not make it clear.\n\nParticipants:\n{}",
+        participants
+    )
+}
@@ -0,0 +1,289 @@
+use crate::analysis::OutputFormat;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeyPoint {
+    pub point: String,
+    pub source_timestamp: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ActionItem {
+    pub description: String,
+    pub owner: Option<String>,
+    pub due_date: Option<String>,
+    pub source_timestamp: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Participant {
+    pub name: String,
+    pub role: Option<String>,
+    pub affiliation: Option<String>,
+    pub first_spoken_at: Option<String>,
+}
+
+// A diarization label matched to a participant by the model
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SpeakerAssignment {
+    pub label: String,
+    pub name: Option<String>,
+    pub role: Option<String>,
+}
+
+// Structured responses are wrapped in an object because the API requires an object at the top level
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct KeyPoints {
+    pub key_points: Vec<KeyPoint>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ActionItems {
+    pub action_items: Vec<ActionItem>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Participants {
+    pub participants: Vec<Participant>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SpeakerAssignments {
+    pub speakers: Vec<SpeakerAssignment>,
+}
+
+// A structured analysis result: its schema and how it reads as plain text
+pub trait StructuredOutput: Serialize + DeserializeOwned {
+    const FORMAT: OutputFormat;
+
+    // One line of text per item, without list markers
+    fn items(&self) -> Vec<String>;
+
+    fn render_text(&self) -> String {
+        self.items()
+            .iter()
+            .map(|item| format!("- {}", item))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn validate<T: StructuredOutput>(output: &str) -> Result<(), String> {
+    serde_json::from_str::<T>(output)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Strict-mode schema for an object holding a single array of objects with the given fields;
+// fields other than `required_field` may be null
+fn list_schema(list_name: &str, required_field: &str, fields: &[(&str, &str)]) -> Value {
+    let mut properties = serde_json::Map::new();
+    for (name, description) in fields {
+        let kind = if *name == required_field {
+            json!("string")
+        } else {
+            json!(["string", "null"])
+        };
+        properties.insert(
+            name.to_string(),
+            json!({ "type": kind, "description": description }),
+        );
+    }
+    let names: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+    json!({
+        "type": "object",
+        "properties": {
+            list_name: {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": properties,
+                    "required": names,
+                    "additionalProperties": false
+                }
+            }
+        },
+        "required": [list_name],
+        "additionalProperties": false
+    })
+}
+
+fn key_points_schema() -> Value {
+    list_schema(
+        "key_points",
+        "point",
+        &[
+            ("point", "One key point, stated in a single sentence"),
+            (
+                "source_timestamp",
+                "HH:MM:SS timestamp of the transcript line the point comes from",
+            ),
+        ],
+    )
+}
+
+fn action_items_schema() -> Value {
+    list_schema(
+        "action_items",
+        "description",
+        &[
+            ("description", "What needs to be done"),
+            ("owner", "Person or party responsible, if stated"),
+            ("due_date", "Deadline as stated in the hearing, if any"),
+            (
+                "source_timestamp",
+                "HH:MM:SS timestamp of the transcript line the item comes from",
+            ),
+        ],
+    )
+}
+
+fn participants_schema() -> Value {
+    list_schema(
+        "participants",
+        "name",
+        &[
+            ("name", "Name of the participant as spoken"),
+            (
+                "role",
+                "Role in the hearing, such as judge, witness or counsel",
+            ),
+            (
+                "affiliation",
+                "Organisation or party the participant represents",
+            ),
+            (
+                "first_spoken_at",
+                "HH:MM:SS timestamp of the first transcript line they speak",
+            ),
+        ],
+    )
+}
+
+fn speakers_schema() -> Value {
+    list_schema(
+        "speakers",
+        "label",
+        &[
+            (
+                "label",
+                "Speaker label exactly as it appears in the transcript",
+            ),
+            (
+                "name",
+                "Name of the participant speaking under this label, if known",
+            ),
+            (
+                "role",
+                "Role in the hearing, such as judge, witness or counsel",
+            ),
+        ],
+    )
+}
+
+fn with_timestamp(text: String, timestamp: &Option<String>) -> String {
+    match timestamp {
+        Some(timestamp) => format!("[{}] {}", timestamp, text),
+        None => text,
+    }
+}
+
+impl StructuredOutput for KeyPoints {
+    const FORMAT: OutputFormat = OutputFormat {
+        name: "key_points",
+        schema: key_points_schema,
+        validate: validate::<KeyPoints>,
+    };
+
+    fn items(&self) -> Vec<String> {
+        self.key_points
+            .iter()
+            .map(|p| with_timestamp(p.point.clone(), &p.source_timestamp))
+            .collect()
+    }
+}
+
+impl StructuredOutput for ActionItems {
+    const FORMAT: OutputFormat = OutputFormat {
+        name: "action_items",
+        schema: action_items_schema,
+        validate: validate::<ActionItems>,
+    };
+
+    fn items(&self) -> Vec<String> {
+        self.action_items
+            .iter()
+            .map(|item| {
+                let mut line = item.description.clone();
+                if let Some(owner) = &item.owner {
+                    line.push_str(&format!(" (owner: {})", owner));
+                }
+                if let Some(due_date) = &item.due_date {
+                    line.push_str(&format!(" (due: {})", due_date));
+                }
+                with_timestamp(line, &item.source_timestamp)
+            })
+            .collect()
+    }
+}
+
+impl StructuredOutput for Participants {
+    const FORMAT: OutputFormat = OutputFormat {
+        name: "participants",
+        schema: participants_schema,
+        validate: validate::<Participants>,
+    };
+
+    fn items(&self) -> Vec<String> {
+        self.participants
+            .iter()
+            .map(|p| {
+                let details: Vec<&str> = [&p.role, &p.affiliation]
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str)
+                    .collect();
+                let mut line = p.name.clone();
+                if !details.is_empty() {
+                    line.push_str(&format!(" ({})", details.join(", ")));
+                }
+                if let Some(first_spoken_at) = &p.first_spoken_at {
+                    line.push_str(&format!(", first spoke at {}", first_spoken_at));
+                }
+                line
+            })
+            .collect()
+    }
+}
+
+impl StructuredOutput for SpeakerAssignments {
+    const FORMAT: OutputFormat = OutputFormat {
+        name: "speakers",
+        schema: speakers_schema,
+        validate: validate::<SpeakerAssignments>,
+    };
+
+    fn items(&self) -> Vec<String> {
+        self.speakers
+            .iter()
+            .map(|speaker| {
+                let mut line = format!(
+                    "{}: {}",
+                    speaker.label,
+                    speaker.name.as_deref().unwrap_or("unknown")
+                );
+                if let Some(role) = &speaker.role {
+                    line.push_str(&format!(" ({})", role));
+                }
+                line
+            })
+            .collect()
+    }
+}
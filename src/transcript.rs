@@ -0,0 +1,152 @@
+use crate::storage::{Storage, StorageError};
+use serde::{Deserialize, Serialize};
+
+// A stretch of speech with its position in the recording, in seconds
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TranscriptSegment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    // Label assigned by diarization, e.g. "Speaker 1"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+}
+
+impl TranscriptSegment {
+    // The segment text, prefixed with its speaker when known
+    pub fn attributed_text(&self) -> String {
+        match &self.speaker {
+            Some(speaker) => format!("{}: {}", speaker, self.text.trim()),
+            None => self.text.trim().to_string(),
+        }
+    }
+}
+
+// Consecutive segments from one speaker, merged
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SpeakerTurn {
+    pub speaker: String,
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+// Storage key of the timestamped segments stored next to `transcriptions/<id>.txt`
+pub fn segments_key(recording_id: &str) -> String {
+    format!("transcriptions/{}.segments.json", recording_id)
+}
+
+// Storage key of the turn-by-turn transcript written when diarization ran
+pub fn turns_key(recording_id: &str) -> String {
+    format!("transcriptions/{}.turns.json", recording_id)
+}
+
+pub fn has_speakers(segments: &[TranscriptSegment]) -> bool {
+    segments.iter().any(|segment| segment.speaker.is_some())
+}
+
+// Render seconds as HH:MM:SS
+pub fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        total / 3600,
+        (total % 3600) / 60,
+        total % 60
+    )
+}
+
+// Join segment texts into the plain transcript stored as `<id>.txt`
+pub fn plain_text(segments: &[TranscriptSegment]) -> String {
+    segments
+        .iter()
+        .map(|segment| segment.text.trim())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// One `[HH:MM:SS] text` line per segment, for analyses that cite timestamps
+pub fn timestamped_text(segments: &[TranscriptSegment]) -> String {
+    segments
+        .iter()
+        .filter(|segment| !segment.text.trim().is_empty())
+        .map(|segment| {
+            format!(
+                "[{}] {}",
+                format_timestamp(segment.start),
+                segment.attributed_text()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Merge runs of segments from the same speaker into turns; segments without a speaker
+// are attributed to "Unknown speaker"
+pub fn speaker_turns(segments: &[TranscriptSegment]) -> Vec<SpeakerTurn> {
+    let mut turns: Vec<SpeakerTurn> = Vec::new();
+    for segment in segments {
+        let text = segment.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let speaker = segment.speaker.as_deref().unwrap_or("Unknown speaker");
+        match turns.last_mut() {
+            Some(turn) if turn.speaker == speaker => {
+                turn.end = segment.end;
+                turn.text.push(' ');
+                turn.text.push_str(text);
+            }
+            _ => turns.push(SpeakerTurn {
+                speaker: speaker.to_string(),
+                start: segment.start,
+                end: segment.end,
+                text: text.to_string(),
+            }),
+        }
+    }
+    turns
+}
+
+// One `Speaker: text` paragraph per turn, the transcript analyses see once speakers are known
+pub fn turn_text(turns: &[SpeakerTurn]) -> String {
+    turns
+        .iter()
+        .map(|turn| format!("{}: {}", turn.speaker, turn.text))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+pub async fn save_segments(
+    storage: &dyn Storage,
+    recording_id: &str,
+    segments: &[TranscriptSegment],
+) -> Result<(), StorageError> {
+    let contents =
+        serde_json::to_vec(segments).map_err(|e| StorageError::Backend(e.to_string()))?;
+    storage.put(&segments_key(recording_id), contents).await
+}
+
+pub async fn save_turns(
+    storage: &dyn Storage,
+    recording_id: &str,
+    turns: &[SpeakerTurn],
+) -> Result<(), StorageError> {
+    let contents = serde_json::to_vec(turns).map_err(|e| StorageError::Backend(e.to_string()))?;
+    storage.put(&turns_key(recording_id), contents).await
+}
+
+// Segments are only available for transcripts produced since they started being stored
+pub async fn load_segments(
+    storage: &dyn Storage,
+    recording_id: &str,
+) -> Result<Option<Vec<TranscriptSegment>>, StorageError> {
+    match storage.get(&segments_key(recording_id)).await {
+        Ok(contents) => serde_json::from_slice(&contents)
+            .map(Some)
+            .map_err(|e| StorageError::Backend(e.to_string())),
+        Err(StorageError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
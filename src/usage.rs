@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+// What one upstream API call consumed
+#[derive(Clone, Debug)]
+pub struct UsageEntry {
+    pub model: String,
+    pub api_key: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub audio_seconds: f64,
+}
+
+// Collects the upstream calls made while serving a request so they can be recorded against
+// the recording and operation they were made for
+#[derive(Default)]
+pub struct UsageMeter {
+    entries: Mutex<Vec<UsageEntry>>,
+}
+
+impl UsageMeter {
+    pub fn add(&self, entry: UsageEntry) {
+        self.entries.lock().unwrap().push(entry);
+    }
+
+    pub fn take(&self) -> Vec<UsageEntry> {
+        std::mem::take(&mut *self.entries.lock().unwrap())
+    }
+}
+
+// Keys are never stored; usage is attributed to a short hash of the key instead
+pub fn api_key_fingerprint(api_key: &str) -> String {
+    let digest = hex::encode(Sha256::digest(api_key.as_bytes()));
+    format!("key-{}", &digest[..12])
+}
+
+// Token counts from the `usage` block of a chat completion response
+pub fn chat_usage(model: &str, api_key: &str, usage: &Value) -> Option<UsageEntry> {
+    let prompt_tokens = usage["prompt_tokens"].as_u64()?;
+    Some(UsageEntry {
+        model: model.to_string(),
+        api_key: api_key_fingerprint(api_key),
+        prompt_tokens,
+        completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+        audio_seconds: 0.0,
+    })
+}
+
+pub fn audio_usage(model: &str, api_key: &str, audio_seconds: f64) -> UsageEntry {
+    UsageEntry {
+        model: model.to_string(),
+        api_key: api_key_fingerprint(api_key),
+        prompt_tokens: 0,
+        completion_tokens: 0,
+        audio_seconds,
+    }
+}
+
+// Prices for one model; tokens are priced per million, audio per minute
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ModelPrice {
+    #[serde(default)]
+    pub input_per_million: f64,
+    #[serde(default)]
+    pub output_per_million: f64,
+    #[serde(default)]
+    pub audio_per_minute: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PriceTable {
+    pub currency: String,
+    pub models: HashMap<String, ModelPrice>,
+}
+
+impl Default for PriceTable {
+    // List prices at the time of writing; override with PRICES_FILE when they change
+    fn default() -> Self {
+        let models = HashMap::from([
+            (
+                "gpt-4o-mini".to_string(),
+                ModelPrice {
+                    input_per_million: 0.15,
+                    output_per_million: 0.60,
+                    audio_per_minute: 0.0,
+                },
+            ),
+            (
+                "whisper-1".to_string(),
+                ModelPrice {
+                    input_per_million: 0.0,
+                    output_per_million: 0.0,
+                    audio_per_minute: 0.006,
+                },
+            ),
+        ]);
+        PriceTable {
+            currency: "USD".to_string(),
+            models,
+        }
+    }
+}
+
+impl PriceTable {
+    // PRICES_FILE points at a JSON file shaped like the default table:
+    // {"currency": "USD", "models": {"gpt-4o-mini": {"input_per_million": 0.15, ...}}}
+    pub fn from_env() -> Result<PriceTable, String> {
+        match env::var("PRICES_FILE") {
+            Ok(path) => {
+                let contents = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+                serde_json::from_str(&contents)
+                    .map_err(|e| format!("Invalid price table in {}: {}", path, e))
+            }
+            Err(_) => Ok(PriceTable::default()),
+        }
+    }
+
+    // Estimated cost, or None when the model has no price
+    pub fn cost(
+        &self,
+        model: &str,
+        prompt_tokens: u64,
+        completion_tokens: u64,
+        audio_seconds: f64,
+    ) -> Option<f64> {
+        let price = self.models.get(model)?;
+        Some(
+            prompt_tokens as f64 / 1_000_000.0 * price.input_per_million
+                + completion_tokens as f64 / 1_000_000.0 * price.output_per_million
+                + audio_seconds / 60.0 * price.audio_per_minute,
+        )
+    }
+}
@@ -0,0 +1,172 @@
+// Terms Whisper should spell the way the court does: case and party names, legal jargon.
+// They are passed as the transcription prompt, which Whisper treats as preceding text.
+
+// Whisper only reads the last 224 tokens of a prompt; stay well under that in characters
+const MAX_PROMPT_CHARS: usize = 800;
+
+// Text from the end of the previous chunk, carried over for continuity across chunk boundaries
+const CONTEXT_CHARS: usize = 250;
+
+pub const MAX_TERMS: usize = 1000;
+pub const MAX_TERM_CHARS: usize = 100;
+
+// Split a comma or newline separated list, dropping blanks and repeats (ignoring case)
+pub fn parse_terms(list: &str) -> Vec<String> {
+    let terms: Vec<String> = list.split([',', '\n']).map(str::to_string).collect();
+    merge(&terms, &[])
+}
+
+// Request terms first, then the organization's; repeats keep their first position
+pub fn merge(request: &[String], organization: &[String]) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for term in request.iter().chain(organization) {
+        let term = term.trim();
+        if !term.is_empty() && !terms.iter().any(|t| t.eq_ignore_ascii_case(term)) {
+            terms.push(term.to_string());
+        }
+    }
+    terms
+}
+
+pub fn validate(terms: &[String]) -> Result<(), String> {
+    if terms.len() > MAX_TERMS {
+        return Err(format!("At most {} terms are allowed", MAX_TERMS));
+    }
+    match terms.iter().find(|t| t.chars().count() > MAX_TERM_CHARS) {
+        Some(term) => Err(format!(
+            "Term is longer than {} characters: {}",
+            MAX_TERM_CHARS, term
+        )),
+        None => Ok(()),
+    }
+}
+
+// The last words of a chunk's transcript, starting at a word boundary
+pub fn context_tail(text: &str) -> String {
+    let text = text.trim();
+    let count = text.chars().count();
+    if count <= CONTEXT_CHARS {
+        return text.to_string();
+    }
+    let tail: String = text.chars().skip(count - CONTEXT_CHARS).collect();
+    match tail.split_once(char::is_whitespace) {
+        Some((_, rest)) => rest.trim_start().to_string(),
+        None => tail,
+    }
+}
+
+// Prompt for one chunk: as many terms as fit, then the previous chunk's tail, which goes last
+// as it is the text Whisper continues from. None when there is nothing to prompt with.
+pub fn whisper_prompt(terms: &[String], previous: Option<&str>) -> Option<String> {
+    let context = previous.map(context_tail).unwrap_or_default();
+    let budget = MAX_PROMPT_CHARS.saturating_sub(context.chars().count());
+
+    let mut glossary = String::new();
+    for term in terms {
+        let addition = if glossary.is_empty() {
+            format!("Glossary: {}", term)
+        } else {
+            format!(", {}", term)
+        };
+        // Room for the closing full stop and the line break before the context
+        if glossary.chars().count() + addition.chars().count() + 2 > budget {
+            break;
+        }
+        glossary.push_str(&addition);
+    }
+    if !glossary.is_empty() {
+        glossary.push('.');
+    }
+
+    let prompt = [glossary, context]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    (!prompt.is_empty()).then_some(prompt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("Term{:03}", i)).collect()
+    }
+
+    #[test]
+    fn no_prompt_without_terms_or_context() {
+        assert_eq!(whisper_prompt(&[], None), None);
+        assert_eq!(whisper_prompt(&[], Some("  ")), None);
+    }
+
+    #[test]
+    fn glossary_lists_terms() {
+        let terms = vec!["Smith v Jones".to_string(), "habeas corpus".to_string()];
+        assert_eq!(
+            whisper_prompt(&terms, None).as_deref(),
+            Some("Glossary: Smith v Jones, habeas corpus.")
+        );
+        assert_eq!(
+            whisper_prompt(&terms, Some("The witness said")).as_deref(),
+            Some("Glossary: Smith v Jones, habeas corpus.\nThe witness said")
+        );
+    }
+
+    #[test]
+    fn glossary_is_cut_at_a_term_to_fit() {
+        let prompt = whisper_prompt(&terms(200), None).unwrap();
+        assert!(prompt.chars().count() <= MAX_PROMPT_CHARS);
+        assert!(prompt.ends_with('.'));
+        // Terms are dropped whole, from the end of the list
+        let listed: Vec<&str> = prompt
+            .trim_start_matches("Glossary: ")
+            .trim_end_matches('.')
+            .split(", ")
+            .collect();
+        assert_eq!(listed, terms(listed.len()));
+        assert!(listed.len() < 200);
+    }
+
+    #[test]
+    fn context_keeps_its_place_when_terms_are_cut() {
+        let previous = "word ".repeat(200);
+        let prompt = whisper_prompt(&terms(200), Some(&previous)).unwrap();
+        assert!(prompt.chars().count() <= MAX_PROMPT_CHARS);
+        let (glossary, context) = prompt.split_once('\n').unwrap();
+        assert!(glossary.starts_with("Glossary: Term000") && glossary.ends_with('.'));
+        assert_eq!(context, context_tail(&previous));
+    }
+
+    #[test]
+    fn context_tail_starts_at_a_word() {
+        let text = format!("{} final words", "abcdefghij ".repeat(50));
+        let tail = context_tail(&text);
+        assert!(tail.chars().count() <= CONTEXT_CHARS);
+        assert!(tail.starts_with("abcdefghij"));
+        assert!(tail.ends_with("final words"));
+        assert_eq!(context_tail("  short text "), "short text");
+    }
+
+    #[test]
+    fn a_term_longer_than_the_budget_is_left_out() {
+        let long = vec!["x".repeat(MAX_PROMPT_CHARS)];
+        assert_eq!(
+            whisper_prompt(&long, Some("context")).as_deref(),
+            Some("context")
+        );
+    }
+
+    #[test]
+    fn merges_terms_without_repeats() {
+        assert_eq!(
+            parse_terms("Smith, smith ,\nJones,, "),
+            ["Smith".to_string(), "Jones".to_string()]
+        );
+        let merged = merge(
+            &["Jones".to_string()],
+            &["JONES".to_string(), "Smyth".to_string()],
+        );
+        assert_eq!(merged, ["Jones".to_string(), "Smyth".to_string()]);
+    }
+}
@@ -0,0 +1,280 @@
+use serde_json::json;
+use std::io::Read;
+use std::process::{Command, Stdio};
+
+// Audio is decoded to mono 16-bit PCM at this rate before peaks are taken; speech needs no more
+pub const SAMPLE_RATE: u32 = 16000;
+
+// Samples summarised by each min/max pair: 100 pairs per second of audio
+pub const SAMPLES_PER_PIXEL: u32 = 160;
+
+// audiowaveform binary format version 2, which adds the channel count to version 1
+const DAT_VERSION: i32 = 2;
+const DAT_HEADER_LEN: usize = 24;
+
+pub fn waveform_key(recording_id: &str) -> String {
+    format!("waveforms/{}.dat", recording_id)
+}
+
+// Downsampled peaks of a mono recording: one (min, max) pair per `samples_per_pixel` samples
+pub struct Waveform {
+    pub sample_rate: u32,
+    pub samples_per_pixel: u32,
+    pub peaks: Vec<(i16, i16)>,
+}
+
+impl Waveform {
+    // Peaks of little-endian 16-bit PCM read to the end of `pcm`
+    pub fn from_pcm(mut pcm: impl Read) -> std::io::Result<Waveform> {
+        let mut peaks = Vec::new();
+        let mut current: Option<(i16, i16)> = None;
+        let mut counted = 0;
+        let mut buffer = vec![0u8; 64 * 1024];
+        // A sample split across reads leaves its first byte at the start of the buffer
+        let mut carried = 0;
+        loop {
+            let read = pcm.read(&mut buffer[carried..])?;
+            if read == 0 {
+                break;
+            }
+            let filled = carried + read;
+            for pair in buffer[..filled].chunks_exact(2) {
+                let sample = i16::from_le_bytes([pair[0], pair[1]]);
+                let (min, max) = current.get_or_insert((sample, sample));
+                *min = (*min).min(sample);
+                *max = (*max).max(sample);
+                counted += 1;
+                if counted == SAMPLES_PER_PIXEL {
+                    peaks.extend(current.take());
+                    counted = 0;
+                }
+            }
+            carried = filled % 2;
+            if carried == 1 {
+                buffer[0] = buffer[filled - 1];
+            }
+        }
+        peaks.extend(current);
+        Ok(Waveform {
+            sample_rate: SAMPLE_RATE,
+            samples_per_pixel: SAMPLES_PER_PIXEL,
+            peaks,
+        })
+    }
+
+    // Merge every `factor` pairs into one, for overviews of long hearings
+    pub fn downsample(&self, factor: u32) -> Waveform {
+        let peaks = self
+            .peaks
+            .chunks(factor.max(1) as usize)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .fold(chunk[0], |(min, max), &(lo, hi)| (min.min(lo), max.max(hi)))
+            })
+            .collect();
+        Waveform {
+            sample_rate: self.sample_rate,
+            samples_per_pixel: self.samples_per_pixel * factor.max(1),
+            peaks,
+        }
+    }
+
+    // audiowaveform binary (.dat) layout: a little-endian header, then interleaved min/max
+    // 16-bit values
+    pub fn to_dat(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(DAT_HEADER_LEN + self.peaks.len() * 4);
+        out.extend_from_slice(&DAT_VERSION.to_le_bytes());
+        // Flags: bit 0 clear means 16-bit values
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&(self.sample_rate as i32).to_le_bytes());
+        out.extend_from_slice(&(self.samples_per_pixel as i32).to_le_bytes());
+        out.extend_from_slice(&(self.peaks.len() as u32).to_le_bytes());
+        out.extend_from_slice(&1i32.to_le_bytes());
+        for (min, max) in &self.peaks {
+            out.extend_from_slice(&min.to_le_bytes());
+            out.extend_from_slice(&max.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn from_dat(data: &[u8]) -> Result<Waveform, String> {
+        if data.len() < DAT_HEADER_LEN {
+            return Err("waveform data is truncated".to_string());
+        }
+        let field = |index: usize| {
+            let start = index * 4;
+            u32::from_le_bytes(data[start..start + 4].try_into().unwrap())
+        };
+        if field(0) as i32 != DAT_VERSION || field(1) & 1 != 0 || field(5) != 1 {
+            return Err("unsupported waveform data".to_string());
+        }
+        let length = field(4) as usize;
+        let values = &data[DAT_HEADER_LEN..];
+        if values.len() != length * 4 {
+            return Err("waveform data is truncated".to_string());
+        }
+        let peaks = values
+            .chunks_exact(4)
+            .map(|pair| {
+                (
+                    i16::from_le_bytes([pair[0], pair[1]]),
+                    i16::from_le_bytes([pair[2], pair[3]]),
+                )
+            })
+            .collect();
+        Ok(Waveform {
+            sample_rate: field(2),
+            samples_per_pixel: field(3),
+            peaks,
+        })
+    }
+
+    // audiowaveform JSON, as read by peaks.js and wavesurfer
+    pub fn to_json(&self) -> serde_json::Value {
+        let data: Vec<i16> = self
+            .peaks
+            .iter()
+            .flat_map(|&(min, max)| [min, max])
+            .collect();
+        json!({
+            "version": DAT_VERSION,
+            "channels": 1,
+            "sample_rate": self.sample_rate,
+            "samples_per_pixel": self.samples_per_pixel,
+            "bits": 16,
+            "length": self.peaks.len(),
+            "data": data,
+        })
+    }
+}
+
+// Decode an audio file with ffmpeg and compute its peaks. The PCM is read as ffmpeg produces
+// it, so hours of audio never sit in memory.
+pub fn generate(input_path: &str) -> Result<Waveform, Box<dyn std::error::Error + Send + Sync>> {
+    let mut child = Command::new("ffmpeg")
+        .arg("-v")
+        .arg("error")
+        .arg("-i")
+        .arg(input_path)
+        .arg("-vn")
+        .arg("-ac")
+        .arg("1")
+        .arg("-ar")
+        .arg(SAMPLE_RATE.to_string())
+        .arg("-f")
+        .arg("s16le")
+        .arg("-acodec")
+        .arg("pcm_s16le")
+        .arg("pipe:1")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take().ok_or("ffmpeg output not captured")?;
+    let waveform = Waveform::from_pcm(stdout);
+    let status = child.wait()?;
+    if !status.success() {
+        return Err("ffmpeg decoding failed".into());
+    }
+    Ok(waveform?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hands out the data a few bytes per read, so samples are split across reads
+    struct Trickle<'a> {
+        data: &'a [u8],
+        sizes: std::iter::Cycle<std::slice::Iter<'a, usize>>,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            let size = (*self.sizes.next().unwrap())
+                .min(buffer.len())
+                .min(self.data.len());
+            buffer[..size].copy_from_slice(&self.data[..size]);
+            self.data = &self.data[size..];
+            Ok(size)
+        }
+    }
+
+    fn pcm(samples: &[i16]) -> Vec<u8> {
+        samples.iter().flat_map(|s| s.to_le_bytes()).collect()
+    }
+
+    // Two full pixels and a partial one
+    fn samples() -> Vec<i16> {
+        (0..2 * SAMPLES_PER_PIXEL as i32 + 10)
+            .map(|i| ((i * 37) % 2000 - 1000) as i16)
+            .chain([i16::MIN, i16::MAX])
+            .collect()
+    }
+
+    fn expected_peaks(samples: &[i16]) -> Vec<(i16, i16)> {
+        samples
+            .chunks(SAMPLES_PER_PIXEL as usize)
+            .map(|chunk| (*chunk.iter().min().unwrap(), *chunk.iter().max().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn peaks_survive_samples_split_across_reads() {
+        let samples = samples();
+        let data = pcm(&samples);
+        for sizes in [&[1usize][..], &[3], &[1, 2, 5], &[4097, 1]] {
+            let reader = Trickle {
+                data: &data,
+                sizes: sizes.iter().cycle(),
+            };
+            let waveform = Waveform::from_pcm(reader).unwrap();
+            assert_eq!(
+                waveform.peaks,
+                expected_peaks(&samples),
+                "reads of {:?}",
+                sizes
+            );
+        }
+    }
+
+    #[test]
+    fn trailing_odd_byte_is_ignored() {
+        let mut data = pcm(&[5, -5, 7]);
+        data.push(0x7f);
+        let waveform = Waveform::from_pcm(&data[..]).unwrap();
+        assert_eq!(waveform.peaks, [(-5, 7)]);
+        assert!(Waveform::from_pcm(&[][..]).unwrap().peaks.is_empty());
+    }
+
+    #[test]
+    fn dat_round_trips() {
+        let waveform = Waveform::from_pcm(&pcm(&samples())[..]).unwrap();
+        let dat = waveform.to_dat();
+        assert_eq!(dat.len(), DAT_HEADER_LEN + waveform.peaks.len() * 4);
+
+        let read = Waveform::from_dat(&dat).unwrap();
+        assert_eq!(read.sample_rate, SAMPLE_RATE);
+        assert_eq!(read.samples_per_pixel, SAMPLES_PER_PIXEL);
+        assert_eq!(read.peaks, waveform.peaks);
+
+        assert!(Waveform::from_dat(&dat[..dat.len() - 1]).is_err());
+        assert!(Waveform::from_dat(&dat[..DAT_HEADER_LEN - 1]).is_err());
+        let mut eight_bit = dat.clone();
+        eight_bit[4] = 1;
+        assert!(Waveform::from_dat(&eight_bit).is_err());
+    }
+
+    #[test]
+    fn downsampling_merges_peaks() {
+        let waveform = Waveform {
+            sample_rate: SAMPLE_RATE,
+            samples_per_pixel: SAMPLES_PER_PIXEL,
+            peaks: vec![(-1, 1), (-3, 2), (0, 5), (-2, 0), (-9, 9)],
+        };
+        let overview = waveform.downsample(2);
+        assert_eq!(overview.samples_per_pixel, 2 * SAMPLES_PER_PIXEL);
+        assert_eq!(overview.peaks, [(-3, 2), (-2, 5), (-9, 9)]);
+        assert_eq!(waveform.downsample(0).peaks, waveform.peaks);
+    }
+}